@@ -0,0 +1,93 @@
+//! Compares the code `json!` expands to against the code it used to
+//! expand to.  Run with `cargo bench`.
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+#[macro_use]
+extern crate json_macros;
+#[cfg(feature="with-rustc-serialize")]
+extern crate rustc_serialize;
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+extern crate serde_json;
+
+#[cfg(feature="with-rustc-serialize")]
+use rustc_serialize::json::Json as Value;
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+use serde_json::Value;
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+use std::hint::black_box;
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+use std::time::{Duration, Instant};
+
+/// Runs `f` for long enough to time it reliably and prints the average
+/// time a run took.
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+fn bench<T, F: FnMut() -> T>(name: &str, mut f: F) {
+    let mut iters = 1u32;
+    loop {
+        let start = Instant::now();
+        for _ in 0..iters {
+            black_box(f());
+        }
+        let elapsed = start.elapsed();
+        if elapsed > Duration::from_millis(200) {
+            println!("{:<40} {:>10} ns/iter", name, (elapsed / iters).as_nanos());
+            return;
+        }
+        iters *= 2;
+    }
+}
+
+/// Benchmarks `json!` building an array of spliced elements against
+/// the `Box<[_]>` round-trip arrays used to be built with.
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+macro_rules! bench_array {
+    ($name:expr, [$($elem:tt),*]) => {
+        bench(concat!($name, ", vec!"), || json!([$($elem),*]));
+        bench(concat!($name, ", Box<[_]>::into_vec"), || {
+            let xs: Box<[_]> = Box::new([$(json!($elem)),*]);
+            Value::Array(xs.into_vec())
+        });
+    }
+}
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+fn main() {
+    let id = black_box(7);
+    // Spliced literals keep `json!` from hoisting the objects they are
+    // in, building them on every run as every object used to be.
+    let one = black_box(1);
+    let max = black_box(60);
+
+    bench("constant subtree, hoisted", || {
+        json!({
+            "id": id,
+            "config": { "retries": [1, 2, 3], "backoff": { "base": 1.5, "max": 60 } }
+        })
+    });
+    bench("constant subtree, built every time", || {
+        json!({
+            "id": id,
+            "config": { "retries": [(one), 2, 3], "backoff": { "base": 1.5, "max": (max) } }
+        })
+    });
+    bench("constant value, borrowed", || {
+        json_static!({ "retries": [1, 2, 3], "backoff": { "base": 1.5, "max": 60 } })
+    });
+
+    bench_array!("array of 3", [(one), (one), (one)]);
+    bench_array!("array of 64", [
+        (one), (one), (one), (one), (one), (one), (one), (one),
+        (one), (one), (one), (one), (one), (one), (one), (one),
+        (one), (one), (one), (one), (one), (one), (one), (one),
+        (one), (one), (one), (one), (one), (one), (one), (one),
+        (one), (one), (one), (one), (one), (one), (one), (one),
+        (one), (one), (one), (one), (one), (one), (one), (one),
+        (one), (one), (one), (one), (one), (one), (one), (one),
+        (one), (one), (one), (one), (one), (one), (one), (one)
+    ]);
+}
+
+// Only the JSON backends provide `json!`.
+#[cfg(not(any(feature="with-rustc-serialize", feature="with-serde")))]
+fn main() {}
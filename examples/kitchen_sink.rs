@@ -1,5 +1,6 @@
-#![feature(plugin)]
-#![plugin(json_macros)]
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+#[macro_use]
+extern crate json_macros;
 
 #[cfg(feature="with-rustc-serialize")]
 extern crate rustc_serialize;
@@ -21,7 +22,7 @@ fn make_pretty_json(x: i32) -> String {
     }).pretty().to_string()
 }
 
-#[cfg(feature="with-serde")]
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
 fn make_pretty_json(x: i32) -> String {
     serde_json::to_string_pretty(&json!({ // object literal
         "foo": "foooooo", // string literal keys and values
@@ -35,8 +36,12 @@ fn make_pretty_json(x: i32) -> String {
     })).unwrap()
 }
 
-
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
 pub fn main() {
     // See implementation for serde/rustc-serialize features above.
     println!("{}", make_pretty_json(1));
 }
+
+// Only the JSON backends provide `json!`.
+#[cfg(not(any(feature="with-rustc-serialize", feature="with-serde")))]
+pub fn main() {}
@@ -0,0 +1,53 @@
+use proc_macro2::{Span, TokenStream};
+
+/// A JSON literal parsed from the input of a `json!` invocation,
+/// independent of the backend it will be emitted for.
+pub struct Json {
+    pub span: Span,
+    pub node: JsonKind,
+}
+
+pub enum JsonKind {
+    /// `null`
+    Null,
+    /// A boolean, numeric or string literal, possibly negated.
+    Lit(TokenStream),
+    /// Any other Rust expression, converted at runtime.
+    Splice(TokenStream),
+    Array(Vec<Element>),
+    Object(Vec<Entry>),
+}
+
+pub enum Element {
+    /// A single value.
+    Value(Json),
+    /// `..expr`, converting and appending every item of an iterable.
+    Spread(syn::Expr),
+    /// `elem if cond`, appending `elem` only if `cond` holds at runtime.
+    If(Box<Element>, syn::Expr),
+    /// `for pat in expr => elem`, appending `elem` for every item of an
+    /// iterable.
+    For(syn::Pat, syn::Expr, Box<Element>),
+}
+
+pub enum Entry {
+    /// `key: value`
+    Pair(Key, Json),
+    /// `key?: expr`, inserting the contents of an `Option` only if it
+    /// is `Some`.
+    Optional(Key, syn::Expr),
+    /// `..expr`, inserting every entry of another object value.
+    Spread(syn::Expr),
+    /// `entry if cond`, inserting `entry` only if `cond` holds at runtime.
+    If(Box<Entry>, syn::Expr),
+    /// `for pat in expr => entry`, inserting `entry` for every item of an
+    /// iterable.
+    For(syn::Pat, syn::Expr, Box<Entry>),
+}
+
+pub enum Key {
+    /// A string literal or bare identifier, known at compile time.
+    Str(syn::LitStr),
+    /// A parenthesized Rust expression, converted to a string at runtime.
+    Expr(TokenStream),
+}
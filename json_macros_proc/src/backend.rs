@@ -0,0 +1,816 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use syn::spanned::Spanned;
+
+use ast::{Element, Entry, Json, JsonKind, Key};
+
+/// Code generation for a JSON library.
+///
+/// The shape of the generated code is shared by every backend in
+/// `emit`; a backend only supplies the paths and conversions specific
+/// to its value type.
+pub trait Backend {
+    /// The type of the values built.
+    fn value_type(&self) -> TokenStream;
+
+    /// The value `null`.
+    fn null(&self, sp: Span) -> TokenStream;
+
+    /// Converts a literal or spliced Rust expression into a value.  If
+    /// the conversion can fail, the failure panics with the name of the
+    /// macro and `path`.
+    fn value(&self, name: &str, expr: TokenStream, path: &Path) -> TokenStream;
+
+    /// Converts a literal or spliced Rust expression into a `Result` of
+    /// a value or a `json_macros::Error` carrying `path`.  Only the JSON
+    /// backends build values fallibly, for `try_json!`.
+    fn try_value(&self, _: TokenStream, _: &Path) -> TokenStream {
+        unreachable!("only the JSON backends build values fallibly")
+    }
+
+    /// Builds a value straight from a literal, for backends that can
+    /// represent it more precisely than converting it at runtime would.
+    /// Returns `None` to leave the literal to `value`.
+    fn lit(&self, _: &TokenStream) -> Option<TokenStream> {
+        None
+    }
+
+    /// Wraps a `Vec` of values into an array value.
+    fn array(&self, sp: Span, vec: TokenStream) -> TokenStream;
+
+    /// Creates an empty map to collect the entries of an object.
+    fn new_object(&self, sp: Span) -> TokenStream;
+
+    /// Converts the `String` an entry is inserted under into a key of
+    /// the map created by `new_object`.
+    fn key(&self, key: TokenStream) -> TokenStream;
+
+    /// Builds the statement inserting a key and a value into a map
+    /// created by `new_object`, replacing the value of an equal key.
+    fn insert(&self, map: TokenStream, key: TokenStream, value: TokenStream) -> TokenStream {
+        quote_expr!(#map.insert(#key, #value);)
+    }
+
+    /// Wraps a map created by `new_object` into an object value.
+    fn object(&self, sp: Span, map: TokenStream) -> TokenStream;
+
+    /// Unwraps an object value into `Some` map of its entries, or `None`
+    /// if it is any other kind of value.
+    fn as_object(&self, sp: Span, value: TokenStream) -> TokenStream;
+}
+
+/// Where a value sits in the value being built, as a JSONPath such as
+/// `$.items[3].id`, for the messages about values that fail to convert.
+///
+/// The indices of elements pushed onto their array and computed keys
+/// are only known at run time: such an index is the length of the
+/// vector of its array before the element is pushed, and a computed key
+/// is bound to a variable before the value inserted under it is built.
+/// Both are named after the depth of their array or object, so that
+/// nested ones don't shadow them.
+#[derive(Clone)]
+pub struct Path(Vec<Segment>);
+
+#[derive(Clone)]
+enum Segment {
+    Key(String),
+    ComputedKey,
+    Index,
+    Nth(usize),
+}
+
+impl Path {
+    pub fn root() -> Path {
+        Path(Vec::new())
+    }
+
+    fn push(&self, segment: Segment) -> Path {
+        let mut path = self.clone();
+        path.0.push(segment);
+        path
+    }
+
+    /// The vector collecting the elements of an array at this path.
+    fn elems(&self) -> Ident {
+        Ident::new(&format!("xs{}", self.0.len()), Span::mixed_site())
+    }
+
+    /// The variable holding a computed key of an object at this path.
+    fn key(&self) -> Ident {
+        Ident::new(&format!("k{}", self.0.len()), Span::mixed_site())
+    }
+
+    /// Builds the `String` displaying the path.
+    pub fn format(&self) -> TokenStream {
+        let mut fmt = String::from("$");
+        let mut args = Vec::new();
+        for (depth, segment) in self.0.iter().enumerate() {
+            let parent = Path(self.0[..depth].to_vec());
+            match *segment {
+                Segment::Key(ref key) if is_identifier(key) => {
+                    fmt.push('.');
+                    fmt.push_str(key);
+                }
+                Segment::Key(ref key) => {
+                    let quoted = format!("[{:?}]", key);
+                    fmt.push_str(&quoted.replace('{', "{{").replace('}', "}}"));
+                }
+                Segment::ComputedKey => {
+                    let key = parent.key();
+                    fmt.push_str("[{:?}]");
+                    args.push(quote_expr!(#key));
+                }
+                Segment::Index => {
+                    let xs = parent.elems();
+                    fmt.push_str("[{}]");
+                    args.push(quote_expr!(#xs.len()));
+                }
+                Segment::Nth(index) => fmt.push_str(&format!("[{}]", index)),
+            }
+        }
+        quote_expr!(::std::format!(#fmt #(, #args)*))
+    }
+}
+
+/// Whether a key can follow a `.` in a path rather than be quoted.
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// How the code building a value goes about it.
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    /// Non-empty arrays and objects containing nothing but literals are
+    /// built once, into a static, and cloned.  Values that fail to
+    /// convert panic.
+    Value,
+    /// As `Value`, but values that fail to convert break out of the
+    /// block labelled `'try_json` with the error.
+    Try,
+    /// Nothing is hoisted, the value being built being a static itself.
+    Static,
+}
+
+pub fn emit<B: Backend>(backend: &B, name: &str, json: Json) -> TokenStream {
+    emit_value(backend, name, json, &Path::root(), Mode::Value)
+}
+
+/// Builds an expression evaluating to a `Result` of the value or the
+/// error of the first value that fails to convert.
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+pub fn emit_try<B: Backend>(backend: &B, name: &str, json: Json) -> TokenStream {
+    let ty = backend.value_type();
+    let value = emit_value(backend, name, json, &Path::root(), Mode::Try);
+    quote_expr!('try_json: {
+        ::std::result::Result::Ok::<#ty, ::json_macros::Error>(#value)
+    })
+}
+
+/// Builds a value in the given mode.
+fn emit_value<B: Backend>(backend: &B, name: &str, json: Json, path: &Path, mode: Mode)
+                          -> TokenStream {
+    if mode != Mode::Static && is_constant(&json) {
+        let hoisted = match json.node {
+            JsonKind::Array(ref elems) => !elems.is_empty(),
+            JsonKind::Object(ref entries) => !entries.is_empty(),
+            _ => false,
+        };
+        if hoisted {
+            let value = emit_static(backend, name, json);
+            return quote_expr!(::std::clone::Clone::clone(#value));
+        }
+    }
+    let sp = json.span;
+    match json.node {
+        JsonKind::Null => backend.null(sp),
+        JsonKind::Lit(expr) => match backend.lit(&expr) {
+            Some(value) => value,
+            None => emit_conversion(backend, name, expr, path, mode),
+        },
+        JsonKind::Splice(expr) => emit_conversion(backend, name, expr, path, mode),
+        JsonKind::Array(elems) => {
+            // An array of plain values is built in one go; the others
+            // push their elements as they go.
+            if elems.iter().all(|elem| matches!(*elem, Element::Value(_))) {
+                let values = elems.into_iter().enumerate().map(|(i, elem)| match elem {
+                    Element::Value(value) => {
+                        emit_value(backend, name, value, &path.push(Segment::Nth(i)), mode)
+                    }
+                    _ => unreachable!(),
+                });
+                return backend.array(sp, quote_expr!(::std::vec![#(#values),*]));
+            }
+            // Room for every element known to be appended; spreads and
+            // comprehensions reserve more as they go.
+            let capacity = elems.iter().filter(|elem| matches!(*elem, Element::Value(_))).count();
+            let pushes = elems.into_iter()
+                .map(|elem| emit_element(backend, name, elem, path, mode));
+            let xs = path.elems();
+            let array = backend.array(sp, quote_expr!(#xs));
+            quote_expr!({
+                let mut #xs = ::std::vec::Vec::with_capacity(#capacity);
+                #(#pushes)*
+                #array
+            })
+        }
+        JsonKind::Object(entries) => {
+            let insertions = entries.into_iter()
+                .map(|entry| emit_entry(backend, name, entry, path, mode));
+            let new_object = backend.new_object(sp);
+            let object = backend.object(sp, quote_expr!(_ob));
+            quote_expr!({
+                let mut _ob = #new_object;
+                #(#insertions)*
+                #object
+            })
+        }
+    }
+}
+
+/// Builds an expression evaluating to a `&'static` reference to a value
+/// containing nothing but literals, built the first time it is
+/// evaluated.
+pub fn emit_static<B: Backend>(backend: &B, name: &str, json: Json) -> TokenStream {
+    let ty = backend.value_type();
+    let value = emit_value(backend, name, json, &Path::root(), Mode::Static);
+    quote_expr!({
+        static VALUE: ::std::sync::OnceLock<#ty> = ::std::sync::OnceLock::new();
+        VALUE.get_or_init(|| #value)
+    })
+}
+
+/// Converts a literal or spliced Rust expression into a value.
+fn emit_conversion<B: Backend>(backend: &B, name: &str, expr: TokenStream, path: &Path,
+                               mode: Mode) -> TokenStream {
+    if mode != Mode::Try {
+        return backend.value(name, expr, path);
+    }
+    let value = backend.try_value(expr, path);
+    quote_expr!(match #value {
+        ::std::result::Result::Ok(value) => value,
+        ::std::result::Result::Err(e) => break 'try_json ::std::result::Result::Err(e),
+    })
+}
+
+/// Unwraps an object value into its map of entries, panicking if it is
+/// any other kind of value.
+pub fn emit_object_entries<B: Backend>(backend: &B, name: &str, sp: Span, value: TokenStream)
+                                       -> TokenStream {
+    let object = backend.as_object(sp, value);
+    let msg = format!("{}!: `..` expects an object value", name);
+    quote_expr!(match #object {
+        ::std::option::Option::Some(map) => map,
+        ::std::option::Option::None => panic!(#msg),
+    })
+}
+
+/// Whether a value contains nothing but literals, so that it is the
+/// same every time it is built.
+pub fn is_constant(json: &Json) -> bool {
+    match json.node {
+        JsonKind::Null | JsonKind::Lit(_) => true,
+        JsonKind::Splice(_) => false,
+        JsonKind::Array(ref elems) => elems.iter().all(|elem| match *elem {
+            Element::Value(ref value) => is_constant(value),
+            _ => false,
+        }),
+        JsonKind::Object(ref entries) => entries.iter().all(|entry| match *entry {
+            Entry::Pair(Key::Str(_), ref value) => is_constant(value),
+            _ => false,
+        }),
+    }
+}
+
+/// Builds the statement appending an element to the vector of the array
+/// at `path`.
+fn emit_element<B: Backend>(backend: &B, name: &str, elem: Element, path: &Path, mode: Mode)
+                            -> TokenStream {
+    let xs = path.elems();
+    let index = path.push(Segment::Index);
+    match elem {
+        Element::Value(value) => {
+            let value = emit_value(backend, name, value, &index, mode);
+            quote_expr!({
+                #xs.push(#value);
+            })
+        }
+        Element::Spread(expr) => {
+            let value = emit_conversion(backend, name, quote_expr!(x), &index, mode);
+            quote_expr!({
+                for x in (#expr) {
+                    #xs.push(#value);
+                }
+            })
+        }
+        Element::If(elem, cond) => {
+            let push = emit_element(backend, name, *elem, path, mode);
+            quote_expr!({
+                if (#cond) {
+                    #push
+                }
+            })
+        }
+        Element::For(pat, expr, elem) => {
+            let push = emit_element(backend, name, *elem, path, mode);
+            quote_expr!({
+                let iter = ::std::iter::IntoIterator::into_iter((#expr));
+                #xs.reserve(::std::iter::Iterator::size_hint(&iter).0);
+                for #pat in iter {
+                    #push
+                }
+            })
+        }
+    }
+}
+
+/// Builds the statement inserting an entry into `_ob`, the map of the
+/// object at `path`.
+fn emit_entry<B: Backend>(backend: &B, name: &str, entry: Entry, path: &Path, mode: Mode)
+                          -> TokenStream {
+    match entry {
+        Entry::Pair(key, value) => {
+            let (k, key, path) = bind_key(key, path);
+            let insert = backend.insert(quote_expr!(_ob), backend.key(quote_expr!(#k)),
+                                        quote_expr!(v));
+            let value = emit_value(backend, name, value, &path, mode);
+            quote_expr!({
+                let #k = #key;
+                let v = #value;
+                #insert
+            })
+        }
+        Entry::Optional(key, expr) => {
+            let (k, key, path) = bind_key(key, path);
+            let insert = backend.insert(quote_expr!(_ob), backend.key(quote_expr!(#k)),
+                                        quote_expr!(v));
+            let value = emit_conversion(backend, name, quote_expr!(v), &path, mode);
+            quote_expr!({
+                if let ::std::option::Option::Some(v) = (#expr) {
+                    let #k = #key;
+                    let v = #value;
+                    #insert
+                }
+            })
+        }
+        Entry::Spread(expr) => {
+            let sp = expr.span();
+            let map = if mode == Mode::Try {
+                let object = backend.as_object(sp, quote_expr!(#expr));
+                let path = path.format();
+                quote_expr!(match #object {
+                    ::std::option::Option::Some(map) => map,
+                    ::std::option::Option::None => {
+                        let e = ::json_macros::Error::new(#path, "`..` expects an object value");
+                        break 'try_json ::std::result::Result::Err(e);
+                    }
+                })
+            } else {
+                emit_object_entries(backend, name, sp, quote_expr!(#expr))
+            };
+            let insert = backend.insert(quote_expr!(_ob), quote_expr!(k), quote_expr!(v));
+            quote_expr!({
+                for (k, v) in #map {
+                    #insert
+                }
+            })
+        }
+        Entry::If(entry, cond) => {
+            let insertion = emit_entry(backend, name, *entry, path, mode);
+            quote_expr!({
+                if (#cond) {
+                    #insertion
+                }
+            })
+        }
+        Entry::For(pat, expr, entry) => {
+            let insertion = emit_entry(backend, name, *entry, path, mode);
+            quote_expr!({
+                for #pat in (#expr) {
+                    #insertion
+                }
+            })
+        }
+    }
+}
+
+/// Names the variable an entry's key is bound to before its value is
+/// built, returning it along with the key and the path of the value.
+fn bind_key(key: Key, path: &Path) -> (Ident, TokenStream, Path) {
+    let segment = match key {
+        Key::Str(ref s) => Segment::Key(s.value()),
+        Key::Expr(_) => Segment::ComputedKey,
+    };
+    (path.key(), emit_key(key), path.push(segment))
+}
+
+/// Builds the `String` an object entry is inserted under.
+pub fn emit_key(key: Key) -> TokenStream {
+    match key {
+        Key::Str(s) => quote_expr!({
+            use ::std::borrow::ToOwned;
+            #s.to_owned()
+        }),
+        Key::Expr(expr) => quote_expr!(::std::string::ToString::to_string(&(#expr))),
+    }
+}
+
+#[cfg(feature="with-rustc-serialize")]
+pub struct RustcSerialize;
+
+#[cfg(feature="with-rustc-serialize")]
+impl Backend for RustcSerialize {
+    fn value_type(&self) -> TokenStream {
+        quote_expr!(::rustc_serialize::json::Json)
+    }
+
+    fn null(&self, _: Span) -> TokenStream {
+        quote_expr!(::rustc_serialize::json::Json::Null)
+    }
+
+    fn value(&self, _: &str, expr: TokenStream, _: &Path) -> TokenStream {
+        quote_expr!({
+            use ::rustc_serialize::json::ToJson;
+            (#expr).to_json()
+        })
+    }
+
+    fn try_value(&self, expr: TokenStream, _: &Path) -> TokenStream {
+        // Converting to `Json` cannot fail.
+        quote_expr!(::std::result::Result::<_, ::json_macros::Error>::Ok({
+            use ::rustc_serialize::json::ToJson;
+            (#expr).to_json()
+        }))
+    }
+
+    fn array(&self, _: Span, vec: TokenStream) -> TokenStream {
+        quote_expr!(::rustc_serialize::json::Json::Array(#vec))
+    }
+
+    fn new_object(&self, _: Span) -> TokenStream {
+        quote_expr!(::std::collections::BTreeMap::new())
+    }
+
+    fn key(&self, key: TokenStream) -> TokenStream {
+        key
+    }
+
+    fn object(&self, _: Span, map: TokenStream) -> TokenStream {
+        quote_expr!(::rustc_serialize::json::Json::Object(#map))
+    }
+
+    fn as_object(&self, _: Span, value: TokenStream) -> TokenStream {
+        quote_expr!(match (#value) {
+            ::rustc_serialize::json::Json::Object(map) => ::std::option::Option::Some(map),
+            _ => ::std::option::Option::None,
+        })
+    }
+}
+
+#[cfg(feature="with-serde")]
+pub struct SerdeJson;
+
+#[cfg(feature="with-serde")]
+impl Backend for SerdeJson {
+    fn value_type(&self) -> TokenStream {
+        quote_expr!(::serde_json::Value)
+    }
+
+    fn null(&self, _: Span) -> TokenStream {
+        quote_expr!(::serde_json::Value::Null)
+    }
+
+    fn value(&self, name: &str, expr: TokenStream, path: &Path) -> TokenStream {
+        let path = path.format();
+        let msg = format!("{}!: cannot convert the value at {{}}: {{}}", name);
+        quote_expr!(match ::serde_json::to_value(&(#expr)) {
+            ::std::result::Result::Ok(value) => value,
+            ::std::result::Result::Err(e) => {
+                panic!(#msg, #path, e)
+            }
+        })
+    }
+
+    fn try_value(&self, expr: TokenStream, path: &Path) -> TokenStream {
+        let path = path.format();
+        quote_expr!(::std::result::Result::map_err(::serde_json::to_value(&(#expr)), |e| {
+            ::json_macros::Error::new(#path, e)
+        }))
+    }
+
+    fn array(&self, _: Span, vec: TokenStream) -> TokenStream {
+        quote_expr!(::serde_json::Value::Array(#vec))
+    }
+
+    fn new_object(&self, _: Span) -> TokenStream {
+        quote_expr!(::serde_json::Map::new())
+    }
+
+    fn key(&self, key: TokenStream) -> TokenStream {
+        key
+    }
+
+    fn object(&self, _: Span, map: TokenStream) -> TokenStream {
+        quote_expr!(::serde_json::Value::Object(#map))
+    }
+
+    fn as_object(&self, _: Span, value: TokenStream) -> TokenStream {
+        quote_expr!(match (#value) {
+            ::serde_json::Value::Object(map) => ::std::option::Option::Some(map),
+            _ => ::std::option::Option::None,
+        })
+    }
+}
+
+#[cfg(feature="with-yaml")]
+pub struct SerdeYaml;
+
+#[cfg(feature="with-yaml")]
+impl Backend for SerdeYaml {
+    fn value_type(&self) -> TokenStream {
+        quote_expr!(::serde_yaml::Value)
+    }
+
+    fn null(&self, _: Span) -> TokenStream {
+        quote_expr!(::serde_yaml::Value::Null)
+    }
+
+    fn value(&self, name: &str, expr: TokenStream, path: &Path) -> TokenStream {
+        let path = path.format();
+        let msg = format!("{}!: cannot convert the value at {{}}: {{}}", name);
+        quote_expr!(match ::serde_yaml::to_value(&(#expr)) {
+            ::std::result::Result::Ok(value) => value,
+            ::std::result::Result::Err(e) => {
+                panic!(#msg, #path, e)
+            }
+        })
+    }
+
+    fn array(&self, _: Span, vec: TokenStream) -> TokenStream {
+        quote_expr!(::serde_yaml::Value::Sequence(#vec))
+    }
+
+    fn new_object(&self, _: Span) -> TokenStream {
+        quote_expr!(::serde_yaml::Mapping::new())
+    }
+
+    fn key(&self, key: TokenStream) -> TokenStream {
+        quote_expr!(::serde_yaml::Value::String(#key))
+    }
+
+    fn object(&self, _: Span, map: TokenStream) -> TokenStream {
+        quote_expr!(::serde_yaml::Value::Mapping(#map))
+    }
+
+    fn as_object(&self, _: Span, value: TokenStream) -> TokenStream {
+        quote_expr!(match (#value) {
+            ::serde_yaml::Value::Mapping(map) => ::std::option::Option::Some(map),
+            _ => ::std::option::Option::None,
+        })
+    }
+}
+
+#[cfg(feature="with-toml")]
+pub struct Toml;
+
+#[cfg(feature="with-toml")]
+impl Backend for Toml {
+    fn value_type(&self) -> TokenStream {
+        quote_expr!(::toml::Value)
+    }
+
+    fn null(&self, sp: Span) -> TokenStream {
+        // Rejected by `check_toml` before anything is emitted.
+        quote_spanned!(sp=> ::std::compile_error!("TOML has no null value"))
+    }
+
+    fn value(&self, name: &str, expr: TokenStream, path: &Path) -> TokenStream {
+        let path = path.format();
+        let msg = format!("{}!: cannot convert the value at {{}}: {{}}", name);
+        quote_expr!(match ::toml::Value::try_from(&(#expr)) {
+            ::std::result::Result::Ok(value) => value,
+            ::std::result::Result::Err(e) => {
+                panic!(#msg, #path, e)
+            }
+        })
+    }
+
+    fn array(&self, _: Span, vec: TokenStream) -> TokenStream {
+        quote_expr!(::toml::Value::Array(#vec))
+    }
+
+    fn new_object(&self, _: Span) -> TokenStream {
+        quote_expr!(::toml::map::Map::new())
+    }
+
+    fn key(&self, key: TokenStream) -> TokenStream {
+        key
+    }
+
+    fn object(&self, _: Span, map: TokenStream) -> TokenStream {
+        quote_expr!(::toml::Value::Table(#map))
+    }
+
+    fn as_object(&self, _: Span, value: TokenStream) -> TokenStream {
+        quote_expr!(match (#value) {
+            ::toml::Value::Table(map) => ::std::option::Option::Some(map),
+            _ => ::std::option::Option::None,
+        })
+    }
+}
+
+/// Parses a byte string literal, which binary formats represent as
+/// bytes rather than as an array of numbers.
+#[cfg(any(feature="with-cbor", feature="with-msgpack"))]
+fn byte_string(tokens: &TokenStream) -> Option<syn::LitByteStr> {
+    syn::parse2(tokens.clone()).ok()
+}
+
+/// Builds the statement inserting into a map kept as a `Vec` of pairs,
+/// as binary formats whose keys may be any value keep them, alongside
+/// the position of each text key in it.  `as_text` borrows the text of
+/// a key if it has any; other keys are found by comparing them with
+/// every pair.
+#[cfg(any(feature="with-cbor", feature="with-msgpack"))]
+fn insert_pair(map: TokenStream, key: TokenStream, value: TokenStream, as_text: TokenStream)
+               -> TokenStream {
+    quote_expr!({
+        let k = #key;
+        let v = #value;
+        match #as_text(&k) {
+            ::std::option::Option::Some(text) => {
+                match #map.1.entry(::std::borrow::ToOwned::to_owned(text)) {
+                    ::std::collections::hash_map::Entry::Occupied(entry) => {
+                        #map.0[*entry.get()].1 = v;
+                    }
+                    ::std::collections::hash_map::Entry::Vacant(entry) => {
+                        entry.insert(#map.0.len());
+                        #map.0.push((k, v));
+                    }
+                }
+            }
+            ::std::option::Option::None => {
+                match #map.0.iter_mut().find(|entry| entry.0 == k) {
+                    ::std::option::Option::Some(entry) => entry.1 = v,
+                    ::std::option::Option::None => #map.0.push((k, v)),
+                }
+            }
+        }
+    })
+}
+
+/// Builds an empty map for `insert_pair`.
+#[cfg(any(feature="with-cbor", feature="with-msgpack"))]
+fn new_pairs(value_type: TokenStream) -> TokenStream {
+    quote_expr!((::std::vec::Vec::<(#value_type, #value_type)>::new(),
+                 ::std::collections::HashMap::<::std::string::String, usize>::new()))
+}
+
+#[cfg(feature="with-cbor")]
+pub struct Ciborium;
+
+#[cfg(feature="with-cbor")]
+impl Backend for Ciborium {
+    fn value_type(&self) -> TokenStream {
+        quote_expr!(::ciborium::Value)
+    }
+
+    fn null(&self, _: Span) -> TokenStream {
+        quote_expr!(::ciborium::Value::Null)
+    }
+
+    fn value(&self, name: &str, expr: TokenStream, path: &Path) -> TokenStream {
+        let path = path.format();
+        let msg = format!("{}!: cannot convert the value at {{}}: {{}}", name);
+        quote_expr!(match ::ciborium::Value::serialized(&(#expr)) {
+            ::std::result::Result::Ok(value) => value,
+            ::std::result::Result::Err(e) => {
+                panic!(#msg, #path, e)
+            }
+        })
+    }
+
+    // CBOR encoders write every number in the fewest bytes that hold it
+    // exactly, so numbers need only keep the type of their literal.
+    fn lit(&self, tokens: &TokenStream) -> Option<TokenStream> {
+        use lit::{self, Number};
+
+        if let Some(bytes) = byte_string(tokens) {
+            let bytes = quote_expr!(::std::vec::Vec::from(&#bytes[..]));
+            return Some(quote_expr!(::ciborium::Value::Bytes(#bytes)));
+        }
+        let (neg, lit) = lit::parse(tokens)?;
+        let n = match lit::number(neg, &lit)? {
+            Number::I64(n) => quote_expr!(#n),
+            Number::U64(n) => quote_expr!(#n),
+            Number::F64(x) if x.is_finite() => {
+                return Some(quote_expr!(::ciborium::Value::Float(#x)));
+            }
+            Number::F64(_) => return None,
+        };
+        Some(quote_expr!(::ciborium::Value::Integer(::std::convert::From::from(#n))))
+    }
+
+    fn array(&self, _: Span, vec: TokenStream) -> TokenStream {
+        quote_expr!(::ciborium::Value::Array(#vec))
+    }
+
+    fn new_object(&self, _: Span) -> TokenStream {
+        new_pairs(self.value_type())
+    }
+
+    fn key(&self, key: TokenStream) -> TokenStream {
+        quote_expr!(::ciborium::Value::Text(#key))
+    }
+
+    fn insert(&self, map: TokenStream, key: TokenStream, value: TokenStream) -> TokenStream {
+        insert_pair(map, key, value, quote_expr!(::ciborium::Value::as_text))
+    }
+
+    fn object(&self, _: Span, map: TokenStream) -> TokenStream {
+        quote_expr!(::ciborium::Value::Map(#map.0))
+    }
+
+    fn as_object(&self, _: Span, value: TokenStream) -> TokenStream {
+        quote_expr!(match (#value) {
+            ::ciborium::Value::Map(map) => ::std::option::Option::Some(map),
+            _ => ::std::option::Option::None,
+        })
+    }
+}
+
+#[cfg(feature="with-msgpack")]
+pub struct Rmpv;
+
+#[cfg(feature="with-msgpack")]
+impl Backend for Rmpv {
+    fn value_type(&self) -> TokenStream {
+        quote_expr!(::rmpv::Value)
+    }
+
+    fn null(&self, _: Span) -> TokenStream {
+        quote_expr!(::rmpv::Value::Nil)
+    }
+
+    fn value(&self, name: &str, expr: TokenStream, path: &Path) -> TokenStream {
+        let path = path.format();
+        let msg = format!("{}!: cannot convert the value at {{}}: {{}}", name);
+        quote_expr!(match ::rmpv::ext::to_value(&(#expr)) {
+            ::std::result::Result::Ok(value) => value,
+            ::std::result::Result::Err(e) => {
+                panic!(#msg, #path, e)
+            }
+        })
+    }
+
+    // MessagePack encoders write integers in the fewest bytes that hold
+    // them, but floats in the width of the value, so a float literal is
+    // kept as an `f32` whenever that loses nothing.
+    fn lit(&self, tokens: &TokenStream) -> Option<TokenStream> {
+        use lit::{self, Number};
+
+        if let Some(bytes) = byte_string(tokens) {
+            let bytes = quote_expr!(::std::vec::Vec::from(&#bytes[..]));
+            return Some(quote_expr!(::rmpv::Value::Binary(#bytes)));
+        }
+        let (neg, lit) = lit::parse(tokens)?;
+        let n = match lit::number(neg, &lit)? {
+            Number::I64(n) => quote_expr!(#n),
+            Number::U64(n) => quote_expr!(#n),
+            Number::F64(x) if x.is_finite() && f64::from(x as f32) == x => {
+                let x = x as f32;
+                return Some(quote_expr!(::rmpv::Value::F32(#x)));
+            }
+            Number::F64(x) if x.is_finite() => return Some(quote_expr!(::rmpv::Value::F64(#x))),
+            Number::F64(_) => return None,
+        };
+        Some(quote_expr!(::rmpv::Value::Integer(::std::convert::From::from(#n))))
+    }
+
+    fn array(&self, _: Span, vec: TokenStream) -> TokenStream {
+        quote_expr!(::rmpv::Value::Array(#vec))
+    }
+
+    fn new_object(&self, _: Span) -> TokenStream {
+        new_pairs(self.value_type())
+    }
+
+    fn key(&self, key: TokenStream) -> TokenStream {
+        quote_expr!(::rmpv::Value::from(#key))
+    }
+
+    fn insert(&self, map: TokenStream, key: TokenStream, value: TokenStream) -> TokenStream {
+        insert_pair(map, key, value, quote_expr!(::rmpv::Value::as_str))
+    }
+
+    fn object(&self, _: Span, map: TokenStream) -> TokenStream {
+        quote_expr!(::rmpv::Value::Map(#map.0))
+    }
+
+    fn as_object(&self, _: Span, value: TokenStream) -> TokenStream {
+        quote_expr!(match (#value) {
+            ::rmpv::Value::Map(map) => ::std::option::Option::Some(map),
+            _ => ::std::option::Option::None,
+        })
+    }
+}
@@ -0,0 +1,579 @@
+use proc_macro2::{Span, TokenStream, TokenTree};
+use syn::parse::{ParseBuffer, ParseStream, Parser};
+
+use ast::{Element, Entry, Json, JsonKind, Key};
+use backend::{self, Backend};
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+use serialize::{self, Serializer, Style};
+
+/// Collects every diagnostic reported while parsing a `json!`
+/// invocation so that they can be emitted together.
+pub struct ExtCtxt {
+    errors: Option<syn::Error>,
+}
+
+impl ExtCtxt {
+    pub fn span_err(&mut self, sp: Span, msg: &str) {
+        self.push_err(syn::Error::new(sp, msg));
+    }
+
+    pub fn push_err(&mut self, err: syn::Error) {
+        match self.errors {
+            Some(ref mut errors) => errors.combine(err),
+            None => self.errors = Some(err),
+        }
+    }
+}
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde", feature="with-yaml",
+          feature="with-cbor", feature="with-msgpack"))]
+pub fn expand<B: Backend>(tts: TokenStream, name: &str, backend: B) -> TokenStream {
+    match parse(tts, name) {
+        Ok(json) => backend::emit(&backend, name, json),
+        Err(errors) => errors,
+    }
+}
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+pub fn expand_try<B: Backend>(tts: TokenStream, name: &str, backend: B) -> TokenStream {
+    match parse(tts, name) {
+        Ok(json) => backend::emit_try(&backend, name, json),
+        Err(errors) => errors,
+    }
+}
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+pub fn expand_static<B: Backend>(tts: TokenStream, name: &str, backend: B) -> TokenStream {
+    let json = match parse(tts, name) {
+        Ok(json) => json,
+        Err(errors) => return errors,
+    };
+    let mut cx = ExtCtxt { errors: None };
+    check_constant(&mut cx, &json, name);
+    match cx.errors {
+        Some(errors) => compile_errors(errors),
+        None => backend::emit_static(&backend, name, json),
+    }
+}
+
+#[cfg(feature="with-toml")]
+pub fn expand_toml<B: Backend>(tts: TokenStream, name: &str, backend: B) -> TokenStream {
+    let json = match parse(tts, name) {
+        Ok(json) => json,
+        Err(errors) => return errors,
+    };
+    let mut cx = ExtCtxt { errors: None };
+    check_toml(&mut cx, &json, name);
+    match cx.errors {
+        Some(errors) => compile_errors(errors),
+        None => backend::emit(&backend, name, json),
+    }
+}
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+pub fn expand_str<S: Serializer>(tts: TokenStream, name: &str, ser: S) -> TokenStream {
+    match parse(tts, name) {
+        Ok(json) => serialize::emit_str(&ser, name, &Style::Compact, json),
+        Err(errors) => errors,
+    }
+}
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+pub fn expand_pretty<S: Serializer>(tts: TokenStream, name: &str, ser: S) -> TokenStream {
+    match parse_with(tts, name, parse_indent) {
+        Ok((indent, json)) => {
+            let style = Style::Pretty(" ".repeat(indent));
+            serialize::emit_str(&ser, name, &style, json)
+        }
+        Err(errors) => errors,
+    }
+}
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+pub fn expand_write<S: Serializer>(tts: TokenStream, name: &str, ser: S) -> TokenStream {
+    match parse_with(tts, name, parse_writer) {
+        Ok((writer, json)) => serialize::emit_write(&ser, name, writer, json),
+        Err(errors) => errors,
+    }
+}
+
+/// Parses the input of a macro invocation, returning its diagnostics as
+/// `compile_error!` invocations if it is malformed.
+fn parse(tts: TokenStream, name: &str) -> Result<Json, TokenStream> {
+    parse_with(tts, name, |_| Ok(())).map(|((), json)| json)
+}
+
+/// Parses the input of a macro invocation taking arguments before the
+/// JSON, which are parsed by `f`.
+fn parse_with<T, F>(tts: TokenStream, name: &str, f: F) -> Result<(T, Json), TokenStream>
+    where F: FnOnce(ParseStream) -> syn::Result<T>
+{
+    let mut cx = ExtCtxt { errors: None };
+    let parser = |input: ParseStream| {
+        let args = f(input)?;
+        let json = parse_json(&mut cx, input);
+        if !input.is_empty() {
+            cx.span_err(input.span(), &format!("expected end of `{}!` macro invocation", name));
+            input.parse::<TokenStream>()?;
+        }
+        Ok((args, json))
+    };
+    let parsed = match parser.parse2(tts) {
+        Ok(parsed) => parsed,
+        Err(err) => return Err(compile_errors(err)),
+    };
+    match cx.errors {
+        Some(errors) => Err(compile_errors(errors)),
+        None => Ok(parsed),
+    }
+}
+
+/// Parses the writer and comma before the JSON of `json_write!`.
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+fn parse_writer(input: ParseStream) -> syn::Result<TokenStream> {
+    let writer = input.parse::<syn::Expr>()?;
+    input.parse::<Token![,]>()?;
+    Ok(quote!(#writer))
+}
+
+/// Parses the optional `indent = N,` before the JSON of `json_pretty!`,
+/// returning the number of spaces to indent by.
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+fn parse_indent(input: ParseStream) -> syn::Result<usize> {
+    if !(input.peek(syn::Ident) && input.peek2(Token![=]) && !input.peek2(Token![==])) {
+        return Ok(2);
+    }
+    let ident = input.parse::<syn::Ident>()?;
+    if ident != "indent" {
+        return Err(syn::Error::new(ident.span(), format!("expected `indent`, found `{}`", ident)));
+    }
+    input.parse::<Token![=]>()?;
+    let indent = match input.parse::<syn::LitInt>() {
+        Ok(lit) => lit.base10_parse()?,
+        Err(err) => return Err(syn::Error::new(err.span(), "expected an integer literal as indent")),
+    };
+    input.parse::<Token![,]>()?;
+    Ok(indent)
+}
+
+/// Turns diagnostics into `compile_error!` invocations, leaving out the
+/// `::core` prefix that `syn::Error::to_compile_error` would emit so the
+/// expansion resolves in 2015 edition crates too.
+fn compile_errors(errors: syn::Error) -> TokenStream {
+    let errors = errors.into_iter().map(|err| {
+        let msg = err.to_string();
+        quote_spanned!(err.span()=> compile_error!(#msg);)
+    });
+    quote!({ #(#errors)* })
+}
+
+/// Parses a comma-separated sequence of elements up to the end of
+/// `input`, which is the contents of a delimited group closed by `ket`.
+///
+/// `f` reports its own errors and returns `None` for a malformed
+/// element, after which the parser skips ahead to the next separator so
+/// that the remaining elements are still checked.
+fn parse_seq<T, F>(cx: &mut ExtCtxt, input: ParseStream, ket: &str, mut f: F) -> Vec<T>
+    where F: FnMut(&mut ExtCtxt, ParseStream) -> Option<T>
+{
+    let mut elems = vec![];
+    while !input.is_empty() {
+        match f(cx, input) {
+            Some(elem) => elems.push(elem),
+            None => skip_to_separator(input),
+        }
+        if input.peek(Token![,]) {
+            let _: Token![,] = input.parse().unwrap();
+        } else if !input.is_empty() {
+            let found = describe(input);
+            cx.span_err(input.span(), &format!("expected `,` or `{}`, found `{}`", ket, found));
+            skip_to_separator(input);
+            if input.peek(Token![,]) {
+                let _: Token![,] = input.parse().unwrap();
+            }
+        }
+    }
+    elems
+}
+
+/// Skips whole token trees until the next `,` or the end of input.
+fn skip_to_separator(input: ParseStream) {
+    while !input.is_empty() && !input.peek(Token![,]) {
+        let _: TokenTree = input.parse().unwrap();
+    }
+}
+
+/// Renders the next token tree for use in a diagnostic.
+fn describe(input: ParseStream) -> String {
+    match input.fork().parse::<TokenTree>() {
+        Ok(TokenTree::Group(g)) => {
+            let mut s = g.to_string();
+            s.truncate(1);
+            s
+        }
+        Ok(tt) => tt.to_string(),
+        Err(_) => "end of input".to_owned(),
+    }
+}
+
+/// Parses an object key, reporting an error at the offending token if
+/// it is missing.  A key is either a string
+/// literal, a bare identifier, which is used as the key's name, or a
+/// parenthesized expression converted with `ToString` at runtime.
+fn parse_key(cx: &mut ExtCtxt, input: ParseStream) -> Option<Key> {
+    use syn::ext::IdentExt;
+    use syn::token::Paren;
+
+    let key = if input.peek(syn::LitStr) {
+        Key::Str(input.parse().unwrap())
+    } else if input.peek(syn::Ident::peek_any) {
+        let id = input.call(syn::Ident::parse_any).unwrap().unraw();
+        Key::Str(syn::LitStr::new(&id.to_string(), id.span()))
+    } else if input.peek(Paren) {
+        let expr = parse_splice(cx, input)?;
+        Key::Expr(quote!(#expr))
+    } else {
+        let found = describe(input);
+        cx.span_err(input.span(),
+                    &format!("expected a string literal, identifier or parenthesized \
+                              expression as object key, found `{}`", found));
+        return None;
+    };
+    Some(key)
+}
+
+/// Reports every part of a value that is not a literal, for macros
+/// building values at most once.
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+fn check_constant(cx: &mut ExtCtxt, json: &Json, name: &str) {
+    use syn::spanned::Spanned;
+
+    match json.node {
+        JsonKind::Null | JsonKind::Lit(_) => {}
+        JsonKind::Splice(_) => literal_err(cx, json.span, name, "a spliced expression"),
+        JsonKind::Array(ref elems) => {
+            for elem in elems {
+                let (sp, found) = match *elem {
+                    Element::Value(ref value) => {
+                        check_constant(cx, value, name);
+                        continue;
+                    }
+                    Element::Spread(ref expr) => (expr.span(), "a spread"),
+                    Element::If(_, ref cond) => (cond.span(), "a conditional element"),
+                    Element::For(ref pat, _, _) => (pat.span(), "a comprehension"),
+                };
+                literal_err(cx, sp, name, found);
+            }
+        }
+        JsonKind::Object(ref entries) => {
+            for entry in entries {
+                let (sp, found) = match *entry {
+                    Entry::Pair(Key::Str(_), ref value) => {
+                        check_constant(cx, value, name);
+                        continue;
+                    }
+                    Entry::Pair(Key::Expr(ref expr), _) => (expr.span(), "a computed key"),
+                    Entry::Optional(_, ref expr) => (expr.span(), "an optional entry"),
+                    Entry::Spread(ref expr) => (expr.span(), "a spread"),
+                    Entry::If(_, ref cond) => (cond.span(), "a conditional entry"),
+                    Entry::For(ref pat, _, _) => (pat.span(), "a comprehension"),
+                };
+                literal_err(cx, sp, name, found);
+            }
+        }
+    }
+}
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+fn literal_err(cx: &mut ExtCtxt, sp: Span, name: &str, found: &str) {
+    cx.span_err(sp, &format!("`{}!` only accepts literals, found {}", name, found));
+}
+
+/// Checks a `toml!` literal against what TOML can represent: there is
+/// no null, and before TOML 1.0 the elements of an array all had to be
+/// of the same type.  Only literals are checked; spliced expressions
+/// that TOML cannot represent fail to convert at runtime.
+#[cfg(feature="with-toml")]
+fn check_toml(cx: &mut ExtCtxt, json: &Json, name: &str) {
+    match json.node {
+        JsonKind::Null => {
+            let msg = format!("`{}!` cannot represent `null`, as TOML has no null value; \
+                               leave the entry out or use `key?: expr`", name);
+            cx.span_err(json.span, &msg);
+        }
+        JsonKind::Lit(_) | JsonKind::Splice(_) => {}
+        JsonKind::Array(ref elems) => {
+            let mut first = None;
+            for value in elems.iter().filter_map(element_value) {
+                check_toml(cx, value, name);
+                let ty = match toml_type(value) {
+                    Some(ty) => ty,
+                    None => continue,
+                };
+                match first {
+                    None => first = Some(ty),
+                    Some(first) if first != ty => {
+                        let msg = format!("`{}!` arrays cannot mix types, found {} after {}",
+                                          name, ty, first);
+                        cx.span_err(value.span, &msg);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        JsonKind::Object(ref entries) => {
+            for value in entries.iter().filter_map(entry_value) {
+                check_toml(cx, value, name);
+            }
+        }
+    }
+}
+
+/// The value an element appends, unless it is a spread.
+#[cfg(feature="with-toml")]
+fn element_value(elem: &Element) -> Option<&Json> {
+    match *elem {
+        Element::Value(ref value) => Some(value),
+        Element::Spread(_) => None,
+        Element::If(ref elem, _) | Element::For(_, _, ref elem) => element_value(elem),
+    }
+}
+
+/// The value an entry inserts, unless it is optional or a spread.
+#[cfg(feature="with-toml")]
+fn entry_value(entry: &Entry) -> Option<&Json> {
+    match *entry {
+        Entry::Pair(_, ref value) => Some(value),
+        Entry::Optional(..) | Entry::Spread(_) => None,
+        Entry::If(ref entry, _) | Entry::For(_, _, ref entry) => entry_value(entry),
+    }
+}
+
+/// Describes the TOML type of a literal, array or object.
+#[cfg(feature="with-toml")]
+fn toml_type(json: &Json) -> Option<&'static str> {
+    use syn::{Expr, ExprLit, ExprUnary, Lit, UnOp};
+
+    let tokens = match json.node {
+        JsonKind::Lit(ref tokens) => tokens,
+        JsonKind::Array(_) => return Some("an array"),
+        JsonKind::Object(_) => return Some("a table"),
+        JsonKind::Null | JsonKind::Splice(_) => return None,
+    };
+    let lit = match syn::parse2::<Expr>(tokens.clone()).ok()? {
+        Expr::Lit(ExprLit { lit, .. }) => lit,
+        Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr, .. }) => match *expr {
+            Expr::Lit(ExprLit { lit, .. }) => lit,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match lit {
+        Lit::Str(_) => Some("a string"),
+        Lit::Int(_) => Some("an integer"),
+        Lit::Float(_) => Some("a float"),
+        Lit::Bool(_) => Some("a boolean"),
+        _ => None,
+    }
+}
+
+/// Reports literal keys written more than once among the unconditional
+/// entries of an object, which would otherwise silently overwrite each
+/// other.
+fn check_duplicate_keys(cx: &mut ExtCtxt, entries: &[Entry]) {
+    use std::collections::HashMap;
+    use std::collections::hash_map::Entry::{Occupied, Vacant};
+
+    let mut seen = HashMap::new();
+    for entry in entries {
+        let key = match *entry {
+            Entry::Pair(Key::Str(ref key), _) | Entry::Optional(Key::Str(ref key), _) => key,
+            _ => continue,
+        };
+        let name = key.value();
+        match seen.entry(name) {
+            Occupied(first) => {
+                let name = first.key();
+                cx.span_err(key.span(), &format!("duplicate key `{}` in object literal", name));
+                cx.span_err(*first.get(), &format!("key `{}` first defined here", name));
+            }
+            Vacant(slot) => {
+                slot.insert(key.span());
+            }
+        }
+    }
+}
+
+/// Parses an optional `if cond` guard following an array element or
+/// object entry, reporting a malformed condition at the `if` token.
+fn parse_guard<T, F>(cx: &mut ExtCtxt, input: ParseStream, item: T, guarded: F) -> Option<T>
+    where F: FnOnce(Box<T>, syn::Expr) -> T
+{
+    let if_token = match input.parse::<Option<Token![if]>>().unwrap() {
+        Some(if_token) => if_token,
+        None => return Some(item),
+    };
+    match input.parse::<syn::Expr>() {
+        Ok(cond) => Some(guarded(Box::new(item), cond)),
+        Err(_) => {
+            cx.span_err(if_token.span, "expected a condition after `if`");
+            None
+        }
+    }
+}
+
+/// Parses the remainder of a comprehension after `for`:
+/// `pat in expr => body`, where `body` is parsed by `f`.
+fn parse_comprehension<T, F, G>(cx: &mut ExtCtxt, input: ParseStream, f: F, comprehension: G)
+                                -> Option<T>
+    where F: FnOnce(&mut ExtCtxt, ParseStream) -> Option<T>,
+          G: FnOnce(syn::Pat, syn::Expr, Box<T>) -> T
+{
+    let pat = match syn::Pat::parse_single(input) {
+        Ok(pat) => pat,
+        Err(err) => {
+            cx.push_err(err);
+            return None;
+        }
+    };
+    if input.parse::<Token![in]>().is_err() {
+        let found = describe(input);
+        cx.span_err(input.span(), &format!("expected `in` after pattern, found `{}`", found));
+        return None;
+    }
+    let expr = parse_splice(cx, input)?;
+    if input.parse::<Token![=>]>().is_err() {
+        let found = describe(input);
+        cx.span_err(input.span(), &format!("expected `=>` after iterator expression, found `{}`",
+                                           found));
+        return None;
+    }
+    f(cx, input).map(|body| comprehension(pat, expr, Box::new(body)))
+}
+
+/// Parses an array element: a value, `..expr` or a comprehension.
+/// Values and spreads may be followed by an `if` guard.
+fn parse_element(cx: &mut ExtCtxt, input: ParseStream) -> Option<Element> {
+    if input.parse::<Option<Token![for]>>().unwrap().is_some() {
+        return parse_comprehension(cx, input, parse_element, Element::For);
+    }
+    let elem = if input.peek(Token![..]) {
+        let _: Token![..] = input.parse().unwrap();
+        Element::Spread(parse_splice(cx, input)?)
+    } else {
+        Element::Value(parse_json(cx, input))
+    };
+    parse_guard(cx, input, elem, Element::If)
+}
+
+/// Parses an object entry: `key: value`, `key?: expr`, `..expr` or a
+/// comprehension.  All but comprehensions may be followed by an `if`
+/// guard.
+fn parse_entry(cx: &mut ExtCtxt, input: ParseStream) -> Option<Entry> {
+    if input.parse::<Option<Token![for]>>().unwrap().is_some() {
+        return parse_comprehension(cx, input, parse_entry, Entry::For);
+    }
+    let entry = parse_unguarded_entry(cx, input)?;
+    parse_guard(cx, input, entry, Entry::If)
+}
+
+fn parse_unguarded_entry(cx: &mut ExtCtxt, input: ParseStream) -> Option<Entry> {
+    if input.peek(Token![..]) {
+        let _: Token![..] = input.parse().unwrap();
+        return parse_splice(cx, input).map(Entry::Spread);
+    }
+    let key = parse_key(cx, input)?;
+    let optional = input.parse::<Option<Token![?]>>().unwrap().is_some();
+    if input.parse::<Token![:]>().is_err() {
+        let found = describe(input);
+        cx.span_err(input.span(), &format!("expected `:` after object key, found `{}`", found));
+        return None;
+    }
+    if optional {
+        parse_splice(cx, input).map(|expr| Entry::Optional(key, expr))
+    } else {
+        Some(Entry::Pair(key, parse_json(cx, input)))
+    }
+}
+
+/// Parses a spliced expression, reporting the parser's own diagnostic
+/// if it is malformed.
+fn parse_splice(cx: &mut ExtCtxt, input: ParseStream) -> Option<syn::Expr> {
+    match input.parse::<syn::Expr>() {
+        Ok(expr) => Some(expr),
+        Err(err) => {
+            cx.push_err(err);
+            skip_to_separator(input);
+            None
+        }
+    }
+}
+
+/// Classifies a parsed expression: plain (possibly negated) literals
+/// are kept apart from other spliced expressions.
+fn splice_kind(expr: syn::Expr) -> JsonKind {
+    use syn::{Expr, ExprUnary, UnOp};
+
+    let is_lit = match expr {
+        Expr::Lit(_) => true,
+        Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr: ref e, .. }) => {
+            matches!(**e, Expr::Lit(_))
+        }
+        _ => false,
+    };
+    if is_lit { JsonKind::Lit(quote!(#expr)) } else { JsonKind::Splice(quote!(#expr)) }
+}
+
+/// Whether the next token can start an expression, in the manner of
+/// libsyntax's `Token::can_begin_expr`.
+fn can_begin_expr(input: ParseStream) -> bool {
+    match input.fork().parse::<TokenTree>() {
+        Ok(TokenTree::Group(_)) | Ok(TokenTree::Ident(_)) | Ok(TokenTree::Literal(_)) => true,
+        Ok(TokenTree::Punct(p)) => "-!*&|<:'.".contains(p.as_char()),
+        Err(_) => false,
+    }
+}
+
+fn bracket_contents<'a>(input: ParseStream<'a>) -> syn::Result<ParseBuffer<'a>> {
+    let content;
+    bracketed!(content in input);
+    Ok(content)
+}
+
+fn brace_contents<'a>(input: ParseStream<'a>) -> syn::Result<ParseBuffer<'a>> {
+    let content;
+    braced!(content in input);
+    Ok(content)
+}
+
+/// Parses a JSON value.  Malformed input is reported through `cx` and
+/// replaced by `null` so that parsing can continue.
+fn parse_json(cx: &mut ExtCtxt, input: ParseStream) -> Json {
+    use syn::token::{Brace, Bracket};
+
+    let span = input.span();
+
+    let node = if input.peek(Bracket) {
+        let content = bracket_contents(input).unwrap();
+        JsonKind::Array(parse_seq(cx, &content, "]", parse_element))
+    } else if input.peek(Brace) {
+        let content = brace_contents(input).unwrap();
+        let entries = parse_seq(cx, &content, "}", parse_entry);
+        check_duplicate_keys(cx, &entries);
+        JsonKind::Object(entries)
+    } else if input.fork().parse::<syn::Ident>().is_ok_and(|id| id == "null") {
+        let _: syn::Ident = input.parse().unwrap();
+        JsonKind::Null
+    } else if can_begin_expr(input) {
+        parse_splice(cx, input).map_or(JsonKind::Null, splice_kind)
+    } else {
+        let found = describe(input);
+        cx.span_err(span, &format!("expected a JSON value, found `{}`", found));
+        if !input.is_empty() && !input.peek(Token![,]) {
+            let _: TokenTree = input.parse().unwrap();
+        }
+        JsonKind::Null
+    };
+
+    Json { span, node }
+}
@@ -0,0 +1,159 @@
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[cfg(feature="with-rustc-serialize")]
+extern crate rustc_serialize;
+#[cfg(feature="with-serde")]
+extern crate serde;
+#[cfg(feature="with-serde")]
+extern crate serde_json;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+
+/// `quote!` for generated code, with the bindings it introduces (`xs`,
+/// `_ob` and the like) resolved at the macro definition site so that
+/// they can neither capture nor shadow variables in spliced expressions.
+macro_rules! quote_expr {
+    ($($tt:tt)*) => {
+        quote_spanned!(::proc_macro2::Span::mixed_site()=> $($tt)*)
+    }
+}
+
+mod ast;
+mod backend;
+mod expand;
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde",
+          feature="with-cbor", feature="with-msgpack"))]
+mod lit;
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+mod serialize;
+
+/// Expands `json!`, using `rustc-serialize` if its feature is enabled
+/// and `serde_json` otherwise.
+#[cfg(feature="with-rustc-serialize")]
+#[proc_macro]
+pub fn json(input: TokenStream) -> TokenStream {
+    expand::expand(input.into(), "json", backend::RustcSerialize).into()
+}
+
+/// Expands `json!`, using `rustc-serialize` if its feature is enabled
+/// and `serde_json` otherwise.
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[proc_macro]
+pub fn json(input: TokenStream) -> TokenStream {
+    expand::expand(input.into(), "json", backend::SerdeJson).into()
+}
+
+/// Expands `try_json!`, using the same backend as `json!`.
+#[cfg(feature="with-rustc-serialize")]
+#[proc_macro]
+pub fn try_json(input: TokenStream) -> TokenStream {
+    expand::expand_try(input.into(), "try_json", backend::RustcSerialize).into()
+}
+
+/// Expands `try_json!`, using the same backend as `json!`.
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[proc_macro]
+pub fn try_json(input: TokenStream) -> TokenStream {
+    expand::expand_try(input.into(), "try_json", backend::SerdeJson).into()
+}
+
+#[cfg(feature="with-rustc-serialize")]
+#[proc_macro]
+pub fn rustc_json(input: TokenStream) -> TokenStream {
+    expand::expand(input.into(), "rustc_json", backend::RustcSerialize).into()
+}
+
+#[cfg(feature="with-serde")]
+#[proc_macro]
+pub fn serde_json(input: TokenStream) -> TokenStream {
+    expand::expand(input.into(), "serde_json", backend::SerdeJson).into()
+}
+
+/// Expands `yaml!`, building a `serde_yaml::Value`.
+#[cfg(feature="with-yaml")]
+#[proc_macro]
+pub fn yaml(input: TokenStream) -> TokenStream {
+    expand::expand(input.into(), "yaml", backend::SerdeYaml).into()
+}
+
+/// Expands `toml!`, building a `toml::Value` after checking that the
+/// literal can be represented in TOML.
+#[cfg(feature="with-toml")]
+#[proc_macro]
+pub fn toml(input: TokenStream) -> TokenStream {
+    expand::expand_toml(input.into(), "toml", backend::Toml).into()
+}
+
+/// Expands `cbor!`, building a `ciborium::Value`.
+#[cfg(feature="with-cbor")]
+#[proc_macro]
+pub fn cbor(input: TokenStream) -> TokenStream {
+    expand::expand(input.into(), "cbor", backend::Ciborium).into()
+}
+
+/// Expands `msgpack!`, building an `rmpv::Value`.
+#[cfg(feature="with-msgpack")]
+#[proc_macro]
+pub fn msgpack(input: TokenStream) -> TokenStream {
+    expand::expand(input.into(), "msgpack", backend::Rmpv).into()
+}
+
+/// Expands `json_static!`, using the same backend as `json!`.
+#[cfg(feature="with-rustc-serialize")]
+#[proc_macro]
+pub fn json_static(input: TokenStream) -> TokenStream {
+    expand::expand_static(input.into(), "json_static", backend::RustcSerialize).into()
+}
+
+/// Expands `json_static!`, using the same backend as `json!`.
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[proc_macro]
+pub fn json_static(input: TokenStream) -> TokenStream {
+    expand::expand_static(input.into(), "json_static", backend::SerdeJson).into()
+}
+
+/// Expands `json_str!`, using the same backend as `json!`.
+#[cfg(feature="with-rustc-serialize")]
+#[proc_macro]
+pub fn json_str(input: TokenStream) -> TokenStream {
+    expand::expand_str(input.into(), "json_str", backend::RustcSerialize).into()
+}
+
+/// Expands `json_str!`, using the same backend as `json!`.
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[proc_macro]
+pub fn json_str(input: TokenStream) -> TokenStream {
+    expand::expand_str(input.into(), "json_str", backend::SerdeJson).into()
+}
+
+/// Expands `json_pretty!`, using the same backend as `json!`.
+#[cfg(feature="with-rustc-serialize")]
+#[proc_macro]
+pub fn json_pretty(input: TokenStream) -> TokenStream {
+    expand::expand_pretty(input.into(), "json_pretty", backend::RustcSerialize).into()
+}
+
+/// Expands `json_pretty!`, using the same backend as `json!`.
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[proc_macro]
+pub fn json_pretty(input: TokenStream) -> TokenStream {
+    expand::expand_pretty(input.into(), "json_pretty", backend::SerdeJson).into()
+}
+
+/// Expands `json_write!`, using the same backend as `json!`.
+#[cfg(feature="with-rustc-serialize")]
+#[proc_macro]
+pub fn json_write(input: TokenStream) -> TokenStream {
+    expand::expand_write(input.into(), "json_write", backend::RustcSerialize).into()
+}
+
+/// Expands `json_write!`, using the same backend as `json!`.
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[proc_macro]
+pub fn json_write(input: TokenStream) -> TokenStream {
+    expand::expand_write(input.into(), "json_write", backend::SerdeJson).into()
+}
@@ -0,0 +1,62 @@
+use proc_macro2::TokenStream;
+use syn::Lit;
+
+/// A number literal evaluated at expansion time.
+pub enum Number {
+    I64(i64),
+    U64(u64),
+    F64(f64),
+}
+
+/// Splits a (possibly negated) literal into its sign and the literal.
+pub fn parse(tokens: &TokenStream) -> Option<(bool, Lit)> {
+    use syn::{Expr, ExprLit, ExprUnary, UnOp};
+
+    match syn::parse2::<Expr>(tokens.clone()).ok()? {
+        Expr::Lit(ExprLit { lit, .. }) => Some((false, lit)),
+        Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr, .. }) => match *expr {
+            Expr::Lit(ExprLit { lit, .. }) => Some((true, lit)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Evaluates a number literal as the type Rust infers for it.  Literals
+/// that would not compile, or whose type is not a primitive integer or
+/// float, are left to runtime.
+pub fn number(neg: bool, lit: &Lit) -> Option<Number> {
+    match *lit {
+        Lit::Int(ref i) => {
+            let magnitude = i128::from(i.base10_parse::<u64>().ok()?);
+            let n = if neg { -magnitude } else { magnitude };
+            let (min, max) = match i.suffix() {
+                "" | "i32" => (i128::from(i32::MIN), i128::from(i32::MAX)),
+                "i8" => (i128::from(i8::MIN), i128::from(i8::MAX)),
+                "i16" => (i128::from(i16::MIN), i128::from(i16::MAX)),
+                "i64" | "isize" => (i128::from(i64::MIN), i128::from(i64::MAX)),
+                "u8" => (0, i128::from(u8::MAX)),
+                "u16" => (0, i128::from(u16::MAX)),
+                "u32" => (0, i128::from(u32::MAX)),
+                "u64" | "usize" => (0, i128::from(u64::MAX)),
+                _ => return None,
+            };
+            if n < min || n > max {
+                None
+            } else if i.suffix().starts_with('u') {
+                Some(Number::U64(n as u64))
+            } else {
+                Some(Number::I64(n as i64))
+            }
+        }
+        Lit::Float(ref f) => {
+            let x = match f.suffix() {
+                "" | "f64" => f.base10_parse::<f64>().ok()?,
+                "f32" => f64::from(f.base10_parse::<f32>().ok()?),
+                _ => return None,
+            };
+            Some(Number::F64(if neg { -x } else { x }))
+        }
+        _ => None,
+    }
+}
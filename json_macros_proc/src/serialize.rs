@@ -0,0 +1,977 @@
+use proc_macro2::TokenStream;
+use syn::spanned::Spanned;
+
+use ast::{Element, Entry, Json, JsonKind, Key};
+use backend::{self, Backend};
+use lit::{self, Number};
+
+/// A JSON value known at expansion time.
+pub enum Constant {
+    Null,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+    Array(Vec<Constant>),
+    Object(Vec<(String, Constant)>),
+}
+
+/// Text output for a JSON library, used by the macros that produce
+/// JSON text rather than a value.
+///
+/// Generated code writes into a buffer of the backend's choosing.  As
+/// with `Backend`, the shape of that code is shared by every backend in
+/// `emit_str`.
+pub trait Serializer: Backend {
+    /// Serializes a constant at expansion time, exactly as the library
+    /// would serialize the equivalent value at runtime.
+    fn serialize(&self, value: &Constant) -> String;
+
+    /// Pretty-prints a constant at expansion time with two spaces of
+    /// indentation, exactly as the library would at runtime.
+    fn serialize_pretty(&self, value: &Constant) -> String;
+
+    /// Creates an empty buffer with room for `capacity` bytes.
+    fn new_buffer(&self, capacity: usize) -> TokenStream;
+
+    /// Appends a `&str` to a buffer.
+    fn write_str(&self, buf: &TokenStream, s: TokenStream) -> TokenStream;
+
+    /// Appends the serialization of a spliced expression to a buffer.
+    fn write_value(&self, buf: &TokenStream, expr: TokenStream) -> TokenStream;
+
+    /// Pretty-prints a spliced expression into a `String` with two
+    /// spaces of indentation.
+    fn pretty_text(&self, expr: TokenStream) -> TokenStream;
+
+    /// Serializes the value a reference points to into the
+    /// `fmt::Formatter` `f`, evaluating to a `fmt::Result`.
+    fn fmt_value(&self, f: &TokenStream, value: TokenStream) -> TokenStream;
+
+    /// Appends the contents of one buffer to another.
+    fn write_buffer(&self, buf: &TokenStream, other: TokenStream) -> TokenStream;
+
+    /// Borrows the contents of a buffer as a `&str`.
+    fn buffer_str(&self, buf: TokenStream) -> TokenStream;
+
+    /// Turns an expression evaluating to a finished buffer into one
+    /// evaluating to a `String`.
+    fn finish(&self, buf: TokenStream) -> TokenStream;
+}
+
+/// Evaluates a JSON literal at expansion time, if it contains nothing
+/// but literals.
+pub fn constant(json: &Json) -> Option<Constant> {
+    match json.node {
+        JsonKind::Null => Some(Constant::Null),
+        JsonKind::Lit(ref tokens) => literal(tokens),
+        JsonKind::Splice(_) => None,
+        JsonKind::Array(ref elems) => {
+            elems.iter().map(|elem| match *elem {
+                Element::Value(ref value) => constant(value),
+                _ => None,
+            }).collect::<Option<_>>().map(Constant::Array)
+        }
+        JsonKind::Object(ref entries) => {
+            entries.iter().map(|entry| match *entry {
+                Entry::Pair(Key::Str(ref key), ref value) => {
+                    constant(value).map(|value| (key.value(), value))
+                }
+                _ => None,
+            }).collect::<Option<_>>().map(Constant::Object)
+        }
+    }
+}
+
+/// Evaluates a (possibly negated) literal the way the type Rust infers
+/// for it would be converted at runtime.  Literals that would not
+/// compile, or whose type has no JSON counterpart, are left to runtime.
+pub fn literal(tokens: &TokenStream) -> Option<Constant> {
+    use syn::Lit;
+
+    let (neg, lit) = lit::parse(tokens)?;
+    match lit {
+        Lit::Bool(ref b) if !neg => Some(Constant::Bool(b.value)),
+        Lit::Str(ref s) if !neg => Some(Constant::String(s.value())),
+        _ => Some(match lit::number(neg, &lit)? {
+            Number::I64(n) => Constant::I64(n),
+            Number::U64(n) => Constant::U64(n),
+            Number::F64(x) => Constant::F64(x),
+        }),
+    }
+}
+
+/// How the macros producing JSON text lay it out.
+pub enum Style {
+    /// No whitespace at all, as the backends serialize values by default.
+    Compact,
+    /// One item per line, indented by the given string for each level of
+    /// nesting, as the backends' pretty printers lay values out.
+    Pretty(String),
+}
+
+/// Re-indents text pretty-printed with two spaces per level to use
+/// `indent` instead, as if nested `depth` levels deep.  Every line but
+/// the first starts with indentation only, since JSON strings cannot
+/// contain raw newlines.
+fn reindent(text: &str, indent: &str, depth: usize) -> String {
+    let mut lines = text.split('\n');
+    let mut out = lines.next().unwrap().to_owned();
+    for line in lines {
+        let trimmed = line.trim_start_matches(' ');
+        out.push('\n');
+        for _ in 0..(line.len() - trimmed.len()) / 2 + depth {
+            out.push_str(indent);
+        }
+        out.push_str(trimmed);
+    }
+    out
+}
+
+/// What generated code writes JSON text into.
+#[derive(Clone, Copy)]
+enum Sink {
+    /// A buffer created by the backend's `new_buffer`.
+    Buffer,
+    /// The `io::Write` or `fmt::Write` passed to `json_write!`, written
+    /// through `write_fmt`, which either trait provides.  Failed writes
+    /// break out of the block labelled `'json_write` with the error.
+    Stream,
+}
+
+/// Accumulates the statements writing JSON text into `buf`, merging
+/// adjacent fixed text into a single write.
+struct Writer<'a, S: 'a> {
+    ser: &'a S,
+    /// The name of the macro being expanded, for runtime panics.
+    name: &'a str,
+    style: &'a Style,
+    sink: Sink,
+    buf: TokenStream,
+    /// How deeply the value being written is nested.
+    depth: usize,
+    text: String,
+    stmts: Vec<TokenStream>,
+    len: usize,
+}
+
+impl<'a, S: Serializer> Writer<'a, S> {
+    fn new(ser: &'a S, name: &'a str, style: &'a Style, sink: Sink, buf: TokenStream,
+           depth: usize) -> Writer<'a, S> {
+        Writer { ser, name, style, sink, buf, depth, text: String::new(), stmts: vec![], len: 0 }
+    }
+
+    /// A writer for a nested block of statements writing to the same
+    /// buffer at the same depth.
+    fn nested(&self) -> Writer<'a, S> {
+        Writer::new(self.ser, self.name, self.style, self.sink, self.buf.clone(), self.depth)
+    }
+
+    /// A writer for a block of statements writing the items of an array
+    /// or object.
+    fn items(&self) -> Writer<'a, S> {
+        Writer::new(self.ser, self.name, self.style, self.sink, self.buf.clone(),
+                    self.depth + 1)
+    }
+
+    fn text(&mut self, s: &str) {
+        self.text.push_str(s);
+        self.len += s.len();
+    }
+
+    fn code(&mut self, code: TokenStream) {
+        self.flush();
+        self.stmts.push(code);
+    }
+
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            let text = &self.text;
+            let stmt = self.write_str(quote_expr!(#text));
+            self.stmts.push(stmt);
+            self.text.clear();
+        }
+    }
+
+    /// Returns the statements written so far, as a block.
+    fn block(mut self) -> TokenStream {
+        self.flush();
+        let stmts = self.stmts;
+        quote_expr!({ #(#stmts)* })
+    }
+
+    /// Builds the statement writing a `&str`.
+    fn write_str(&self, s: TokenStream) -> TokenStream {
+        match self.sink {
+            Sink::Buffer => self.ser.write_str(&self.buf, s),
+            Sink::Stream => stream(s),
+        }
+    }
+
+    /// Builds the statement writing the serialization of a spliced
+    /// expression.
+    fn write_value(&self, expr: TokenStream) -> TokenStream {
+        match self.sink {
+            Sink::Buffer => self.ser.write_value(&self.buf, expr),
+            Sink::Stream => {
+                let fmt = self.ser.fmt_value(&quote_expr!(f), quote_expr!(v));
+                let write = stream(quote_expr!(JsonWriteDisplay(|f: &mut ::std::fmt::Formatter| #fmt)));
+                quote_expr!({
+                    let v = &(#expr);
+                    #write
+                })
+            }
+        }
+    }
+
+    /// Builds the statement writing the contents of a buffer.
+    fn write_buffer(&self, other: TokenStream) -> TokenStream {
+        match self.sink {
+            Sink::Buffer => self.ser.write_buffer(&self.buf, other),
+            Sink::Stream => stream(self.ser.buffer_str(other)),
+        }
+    }
+
+    /// A line break followed by the indentation for `depth`, or nothing
+    /// for compact text.
+    fn newline(&self, depth: usize) -> String {
+        match *self.style {
+            Style::Compact => String::new(),
+            Style::Pretty(ref indent) => format!("\n{}", indent.repeat(depth)),
+        }
+    }
+
+    fn colon(&mut self) {
+        match *self.style {
+            Style::Compact => self.text(":"),
+            Style::Pretty(_) => self.text(": "),
+        }
+    }
+
+    fn constant(&mut self, value: &Constant) {
+        let text = match *self.style {
+            Style::Compact => self.ser.serialize(value),
+            Style::Pretty(ref indent) => {
+                reindent(&self.ser.serialize_pretty(value), indent, self.depth)
+            }
+        };
+        self.text(&text);
+    }
+
+    /// Writes a spliced expression.  Pretty-printed values are
+    /// re-indented as they are written to nest at the current depth.
+    fn value(&mut self, expr: TokenStream) {
+        let indent = match *self.style {
+            Style::Compact => {
+                let write = self.write_value(expr);
+                return self.code(write);
+            }
+            Style::Pretty(ref indent) => indent,
+        };
+        let text = self.ser.pretty_text(expr);
+        let first = self.write_str(quote_expr!(lines.next().unwrap()));
+        let newline = self.newline(self.depth);
+        let newline = self.write_str(quote_expr!(#newline));
+        let indent = self.write_str(quote_expr!(#indent));
+        let trimmed = self.write_str(quote_expr!(trimmed));
+        self.code(quote_expr!({
+            let text = #text;
+            let mut lines = text.split('\n');
+            #first
+            for line in lines {
+                let trimmed = line.trim_start_matches(' ');
+                #newline
+                for _ in 0..(line.len() - trimmed.len()) / 2 {
+                    #indent
+                }
+                #trimmed
+            }
+        }));
+    }
+
+    /// Writes what comes before an item of an array or object written
+    /// by an `items` writer: a separator for every item but the first,
+    /// tracked at runtime by `first`, and a line break if pretty
+    /// printing.
+    fn separator(&mut self) {
+        let comma = self.write_str(quote_expr!(","));
+        self.code(quote_expr!({
+            if !first {
+                #comma
+            }
+            first = false;
+        }));
+        let newline = self.newline(self.depth);
+        self.text(&newline);
+    }
+
+    /// Wraps the statements of an `items` writer, which writes a
+    /// varying number of items, in a block that ends the last line if
+    /// any were written.
+    fn sequence(&mut self, items: Writer<S>) {
+        let stmts = items.block();
+        let newline = self.newline(self.depth);
+        let end = if newline.is_empty() {
+            quote_expr!()
+        } else {
+            let write = self.write_str(quote_expr!(#newline));
+            quote_expr!(if !first {
+                #write
+            })
+        };
+        self.code(quote_expr!({
+            let mut first = true;
+            #stmts
+            #end
+        }));
+    }
+}
+
+/// Builds an expression producing the JSON text of `json`: a
+/// `&'static str` if it is constant, and otherwise a `String` written
+/// without building an intermediate value.
+pub fn emit_str<S: Serializer>(ser: &S, name: &str, style: &Style, json: Json) -> TokenStream {
+    if let Some(value) = constant(&json) {
+        let mut w = Writer::new(ser, name, style, Sink::Buffer, quote_expr!(w), 0);
+        w.constant(&value);
+        let s = w.text;
+        return quote_expr!(#s);
+    }
+    ser.finish(buffer(ser, name, style, 0, |w| write_json(w, json)))
+}
+
+/// Builds an expression writing the compact JSON text of `json` to
+/// `writer`, a mutable reference to an `io::Write` or `fmt::Write`, and
+/// evaluating to an `io::Result<()>`.
+pub fn emit_write<S: Serializer>(ser: &S, name: &str, writer: TokenStream, json: Json)
+                                 -> TokenStream {
+    let mut w = Writer::new(ser, name, &Style::Compact, Sink::Stream, quote_expr!(w), 0);
+    write_json(&mut w, json);
+    let stmts = w.block();
+    quote_expr!({
+        #[allow(unused_imports)]
+        use ::std::fmt::Write as _;
+        #[allow(unused_imports)]
+        use ::std::io::Write as _;
+
+        // The two traits' `write_fmt` fail with different errors.
+        trait JsonWriteResult {
+            fn into_io(self) -> ::std::io::Result<()>;
+        }
+
+        impl JsonWriteResult for ::std::io::Result<()> {
+            fn into_io(self) -> ::std::io::Result<()> {
+                self
+            }
+        }
+
+        impl JsonWriteResult for ::std::fmt::Result {
+            fn into_io(self) -> ::std::io::Result<()> {
+                self.map_err(|_| {
+                    ::std::io::Error::new(::std::io::ErrorKind::Other, "formatter error")
+                })
+            }
+        }
+
+        struct JsonWriteDisplay<F>(F);
+
+        impl<F> ::std::fmt::Display for JsonWriteDisplay<F>
+            where F: Fn(&mut ::std::fmt::Formatter) -> ::std::fmt::Result
+        {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                (self.0)(f)
+            }
+        }
+
+        let w = &mut *(#writer);
+        let result: ::std::io::Result<()> = 'json_write: {
+            #stmts
+            ::std::result::Result::Ok(())
+        };
+        result
+    })
+}
+
+/// Builds the statement writing a `Display` expression to the writer of
+/// `json_write!`.
+fn stream(display: TokenStream) -> TokenStream {
+    quote_expr!({
+        let result = w.write_fmt(format_args!("{}", #display));
+        if let ::std::result::Result::Err(e) = JsonWriteResult::into_io(result) {
+            break 'json_write ::std::result::Result::Err(e);
+        }
+    })
+}
+
+/// Builds an expression evaluating to a new buffer holding the text
+/// written by `f` for a value nested `depth` levels deep.
+fn buffer<S, F>(ser: &S, name: &str, style: &Style, depth: usize, f: F) -> TokenStream
+    where S: Serializer,
+          F: FnOnce(&mut Writer<S>)
+{
+    let mut w = Writer::new(ser, name, style, Sink::Buffer, quote_expr!(w), depth);
+    f(&mut w);
+    let new_buffer = ser.new_buffer(w.len);
+    let stmts = w.block();
+    quote_expr!({
+        let mut w = #new_buffer;
+        #stmts
+        w
+    })
+}
+
+fn write_json<S: Serializer>(w: &mut Writer<S>, json: Json) {
+    if let Some(value) = constant(&json) {
+        return w.constant(&value);
+    }
+    match json.node {
+        JsonKind::Null => w.text("null"),
+        JsonKind::Lit(expr) | JsonKind::Splice(expr) => w.value(expr),
+        JsonKind::Array(elems) => write_array(w, elems),
+        JsonKind::Object(entries) => write_object(w, entries),
+    }
+}
+
+fn write_array<S: Serializer>(w: &mut Writer<S>, elems: Vec<Element>) {
+    if elems.is_empty() {
+        return w.text("[]");
+    }
+    w.text("[");
+    if elems.iter().all(|elem| matches!(*elem, Element::Value(_))) {
+        let newline = w.newline(w.depth + 1);
+        for (i, elem) in elems.into_iter().enumerate() {
+            if i > 0 {
+                w.text(",");
+            }
+            w.text(&newline);
+            w.depth += 1;
+            match elem {
+                Element::Value(value) => write_json(w, value),
+                _ => unreachable!(),
+            }
+            w.depth -= 1;
+        }
+        let newline = w.newline(w.depth);
+        w.text(&newline);
+    } else {
+        let mut items = w.items();
+        for elem in elems {
+            write_element(&mut items, elem);
+        }
+        w.sequence(items);
+    }
+    w.text("]");
+}
+
+fn write_element<S: Serializer>(w: &mut Writer<S>, elem: Element) {
+    match elem {
+        Element::Value(value) => {
+            w.separator();
+            write_json(w, value);
+        }
+        Element::Spread(expr) => {
+            let mut body = w.nested();
+            body.separator();
+            body.value(quote_expr!(x));
+            let body = body.block();
+            w.code(quote_expr!({
+                for x in (#expr) #body
+            }));
+        }
+        Element::If(elem, cond) => {
+            let mut body = w.nested();
+            write_element(&mut body, *elem);
+            let body = body.block();
+            w.code(quote_expr!({
+                if (#cond) #body
+            }));
+        }
+        Element::For(pat, expr, elem) => {
+            let mut body = w.nested();
+            write_element(&mut body, *elem);
+            let body = body.block();
+            w.code(quote_expr!({
+                for #pat in (#expr) #body
+            }));
+        }
+    }
+}
+
+/// The literal key an entry is written under, if every path through it
+/// ends in a `key: value` or `key?: expr` with a literal key.
+fn literal_key(entry: &Entry) -> Option<String> {
+    match *entry {
+        Entry::Pair(Key::Str(ref key), _) | Entry::Optional(Key::Str(ref key), _) => {
+            Some(key.value())
+        }
+        Entry::If(ref entry, _) => literal_key(entry),
+        _ => None,
+    }
+}
+
+/// Writes an object with its keys in the order the library's own maps
+/// would hold them: sorted, or in the order they were first inserted
+/// with the `preserve_order` feature.  Literal keys are ordered at
+/// expansion time; objects with computed keys, spreads, comprehensions
+/// or a literal key written more than once are collected into a map of
+/// serialized values first, except by `json_write!`, which streams
+/// their entries in the order they are produced instead.
+fn write_object<S: Serializer>(w: &mut Writer<S>, entries: Vec<Entry>) {
+    if entries.is_empty() {
+        return w.text("{}");
+    }
+    let mut keys = entries.iter().map(literal_key).collect::<Option<Vec<_>>>();
+    if let Some(ref mut keys) = keys {
+        keys.sort();
+        keys.dedup();
+    }
+    if keys.is_none_or(|keys| keys.len() != entries.len()) {
+        return write_map(w, entries);
+    }
+
+    let mut entries = entries;
+    if !cfg!(feature="preserve_order") {
+        entries.sort_by_key(|entry| literal_key(entry).unwrap());
+    }
+    w.text("{");
+    if entries.iter().all(|entry| matches!(*entry, Entry::Pair(..))) {
+        let newline = w.newline(w.depth + 1);
+        for (i, entry) in entries.into_iter().enumerate() {
+            if i > 0 {
+                w.text(",");
+            }
+            w.text(&newline);
+            w.depth += 1;
+            match entry {
+                Entry::Pair(Key::Str(key), value) => {
+                    w.text(&w.ser.serialize(&Constant::String(key.value())));
+                    w.colon();
+                    write_json(w, value);
+                }
+                _ => unreachable!(),
+            }
+            w.depth -= 1;
+        }
+        let newline = w.newline(w.depth);
+        w.text(&newline);
+    } else {
+        let mut items = w.items();
+        for entry in entries {
+            write_entry(&mut items, entry);
+        }
+        w.sequence(items);
+    }
+    w.text("}");
+}
+
+/// Writes an entry in place: one with a literal key as sorted by
+/// `write_object`, or any entry of an object streamed by `write_map`.
+fn write_entry<S: Serializer>(w: &mut Writer<S>, entry: Entry) {
+    match entry {
+        Entry::Pair(key, value) => {
+            w.separator();
+            write_key(w, key);
+            w.colon();
+            write_json(w, value);
+        }
+        Entry::Optional(key, expr) => {
+            let mut body = w.nested();
+            body.separator();
+            write_key(&mut body, key);
+            body.colon();
+            body.value(quote_expr!(v));
+            let body = body.block();
+            w.code(quote_expr!({
+                if let ::std::option::Option::Some(v) = (#expr) #body
+            }));
+        }
+        Entry::Spread(expr) => {
+            let map = backend::emit_object_entries(w.ser, w.name, expr.span(), quote_expr!(#expr));
+            let mut body = w.nested();
+            body.separator();
+            let key = body.write_value(quote_expr!(k));
+            body.code(key);
+            body.colon();
+            body.value(quote_expr!(v));
+            let body = body.block();
+            w.code(quote_expr!({
+                for (k, v) in #map #body
+            }));
+        }
+        Entry::If(entry, cond) => {
+            let mut body = w.nested();
+            write_entry(&mut body, *entry);
+            let body = body.block();
+            w.code(quote_expr!({
+                if (#cond) #body
+            }));
+        }
+        Entry::For(pat, expr, entry) => {
+            let mut body = w.nested();
+            write_entry(&mut body, *entry);
+            let body = body.block();
+            w.code(quote_expr!({
+                for #pat in (#expr) #body
+            }));
+        }
+    }
+}
+
+/// Writes the key of an entry, computed keys as the strings they are
+/// inserted under.
+fn write_key<S: Serializer>(w: &mut Writer<S>, key: Key) {
+    match key {
+        Key::Str(key) => w.text(&w.ser.serialize(&Constant::String(key.value()))),
+        Key::Expr(_) => {
+            let key = backend::emit_key(key);
+            let write = w.write_value(key);
+            w.code(write);
+        }
+    }
+}
+
+/// Writes an object whose keys are only known at runtime.  Its entries
+/// are serialized into a map ordered as the library's own, or streamed
+/// as they are produced when writing to the writer of `json_write!`,
+/// where a key produced more than once is written more than once.
+fn write_map<S: Serializer>(w: &mut Writer<S>, entries: Vec<Entry>) {
+    if let Sink::Stream = w.sink {
+        w.text("{");
+        let mut items = w.items();
+        for entry in entries {
+            write_entry(&mut items, entry);
+        }
+        w.sequence(items);
+        return w.text("}");
+    }
+    let (ser, name, style, depth) = (w.ser, w.name, w.style, w.depth + 1);
+    let insertions = entries.into_iter()
+        .map(|entry| emit_insertion(ser, name, style, depth, entry));
+    let mut body = w.items();
+    body.separator();
+    let key = body.write_value(quote_expr!(k));
+    body.code(key);
+    body.colon();
+    let value = body.write_buffer(quote_expr!(v));
+    body.code(value);
+    let body = body.block();
+    let mut items = w.items();
+    let map = if cfg!(feature="preserve_order") {
+        quote_expr!(_ob.1.iter().map(|&(ref k, ref v)| (k, v)))
+    } else {
+        quote_expr!(&_ob)
+    };
+    items.code(quote_expr!({
+        for (k, v) in #map #body
+    }));
+    let mut object = w.nested();
+    object.sequence(items);
+    let object = object.block();
+    let new_map = if cfg!(feature="preserve_order") {
+        quote_expr!((::std::collections::BTreeMap::<::std::string::String, usize>::new(),
+                     ::std::vec::Vec::<(::std::string::String, _)>::new()))
+    } else {
+        quote_expr!(::std::collections::BTreeMap::new())
+    };
+    w.text("{");
+    w.code(quote_expr!({
+        let mut _ob = #new_map;
+        #(#insertions)*
+        #object
+    }));
+    w.text("}");
+}
+
+/// Builds the statement inserting `value` under `key` into the map `_ob`
+/// used by `write_map`.  With the `preserve_order` feature, the map is a
+/// vector of entries in the order their keys were first inserted,
+/// alongside the position of each key in it, and a key inserted again
+/// keeps its position, as it would in the library's own maps.
+fn insert(key: TokenStream, value: TokenStream) -> TokenStream {
+    if cfg!(feature="preserve_order") {
+        quote_expr!({
+            let v = #value;
+            match _ob.0.entry(#key) {
+                ::std::collections::btree_map::Entry::Occupied(entry) => {
+                    _ob.1[*entry.get()].1 = v;
+                }
+                ::std::collections::btree_map::Entry::Vacant(entry) => {
+                    _ob.1.push((::std::clone::Clone::clone(entry.key()), v));
+                    entry.insert(_ob.1.len() - 1);
+                }
+            }
+        })
+    } else {
+        quote_expr!({
+            _ob.insert(#key, #value);
+        })
+    }
+}
+
+/// Builds the statement inserting an entry, with its value serialized
+/// into a buffer of its own, into the map `_ob` used by `write_map`.
+fn emit_insertion<S: Serializer>(ser: &S, name: &str, style: &Style, depth: usize,
+                                 entry: Entry) -> TokenStream {
+    match entry {
+        Entry::Pair(key, value) => {
+            let key = backend::emit_key(key);
+            let value = buffer(ser, name, style, depth, |w| write_json(w, value));
+            insert(key, value)
+        }
+        Entry::Optional(key, expr) => {
+            let key = backend::emit_key(key);
+            let value = buffer(ser, name, style, depth, |w| w.value(quote_expr!(v)));
+            let insertion = insert(key, value);
+            quote_expr!({
+                if let ::std::option::Option::Some(v) = (#expr) {
+                    #insertion
+                }
+            })
+        }
+        Entry::Spread(expr) => {
+            let map = backend::emit_object_entries(ser, name, expr.span(), quote_expr!(#expr));
+            let value = buffer(ser, name, style, depth, |w| w.value(quote_expr!(v)));
+            let insertion = insert(quote_expr!(k), value);
+            quote_expr!({
+                for (k, v) in #map {
+                    #insertion
+                }
+            })
+        }
+        Entry::If(entry, cond) => {
+            let insertion = emit_insertion(ser, name, style, depth, *entry);
+            quote_expr!({
+                if (#cond) {
+                    #insertion
+                }
+            })
+        }
+        Entry::For(pat, expr, entry) => {
+            let insertion = emit_insertion(ser, name, style, depth, *entry);
+            quote_expr!({
+                for #pat in (#expr) {
+                    #insertion
+                }
+            })
+        }
+    }
+}
+
+#[cfg(feature="with-rustc-serialize")]
+fn to_json(value: &Constant) -> ::rustc_serialize::json::Json {
+    use rustc_serialize::json::Json;
+
+    match *value {
+        Constant::Null => Json::Null,
+        Constant::Bool(b) => Json::Boolean(b),
+        Constant::I64(n) => Json::I64(n),
+        Constant::U64(n) => Json::U64(n),
+        Constant::F64(x) => Json::F64(x),
+        Constant::String(ref s) => Json::String(s.clone()),
+        Constant::Array(ref elems) => Json::Array(elems.iter().map(to_json).collect()),
+        Constant::Object(ref entries) => {
+            Json::Object(entries.iter().map(|(k, v)| (k.clone(), to_json(v))).collect())
+        }
+    }
+}
+
+#[cfg(feature="with-rustc-serialize")]
+impl Serializer for backend::RustcSerialize {
+    fn serialize(&self, value: &Constant) -> String {
+        to_json(value).to_string()
+    }
+
+    fn serialize_pretty(&self, value: &Constant) -> String {
+        to_json(value).pretty().to_string()
+    }
+
+    fn new_buffer(&self, capacity: usize) -> TokenStream {
+        quote_expr!(::std::string::String::with_capacity(#capacity))
+    }
+
+    fn write_str(&self, buf: &TokenStream, s: TokenStream) -> TokenStream {
+        quote_expr!(#buf.push_str(#s);)
+    }
+
+    fn write_value(&self, buf: &TokenStream, expr: TokenStream) -> TokenStream {
+        quote_expr!({
+            use ::std::fmt::Write;
+            write!(#buf, "{}", ::rustc_serialize::json::as_json(&(#expr)))
+                .expect("json_macros: failed to serialize a spliced value");
+        })
+    }
+
+    fn pretty_text(&self, expr: TokenStream) -> TokenStream {
+        quote_expr!({
+            use ::std::fmt::Write;
+            let mut s = ::std::string::String::new();
+            write!(s, "{}", ::rustc_serialize::json::as_pretty_json(&(#expr)))
+                .expect("json_macros: failed to serialize a spliced value");
+            s
+        })
+    }
+
+    fn fmt_value(&self, f: &TokenStream, value: TokenStream) -> TokenStream {
+        quote_expr!({
+            let mut encoder = ::rustc_serialize::json::Encoder::new(#f);
+            match ::rustc_serialize::Encodable::encode(#value, &mut encoder) {
+                ::std::result::Result::Ok(()) => ::std::result::Result::Ok(()),
+                ::std::result::Result::Err(::rustc_serialize::json::EncoderError::FmtError(e)) => {
+                    ::std::result::Result::Err(e)
+                }
+                ::std::result::Result::Err(_) => {
+                    panic!("json_macros: failed to serialize a spliced value")
+                }
+            }
+        })
+    }
+
+    fn write_buffer(&self, buf: &TokenStream, other: TokenStream) -> TokenStream {
+        quote_expr!(#buf.push_str(&(#other));)
+    }
+
+    fn buffer_str(&self, buf: TokenStream) -> TokenStream {
+        quote_expr!(::std::string::String::as_str(#buf))
+    }
+
+    fn finish(&self, buf: TokenStream) -> TokenStream {
+        buf
+    }
+}
+
+/// The entries of a constant object in the order they are written in
+/// JSON text: sorted, or in the order they were first inserted with the
+/// `preserve_order` feature.  A key written again keeps its position.
+#[cfg(feature="with-serde")]
+fn ordered_entries(entries: &[(String, Constant)]) -> Vec<(&str, &Constant)> {
+    let mut ordered: Vec<(&str, &Constant)> = vec![];
+    for (key, value) in entries {
+        match ordered.iter_mut().find(|entry| entry.0 == key) {
+            Some(entry) => entry.1 = value,
+            None => ordered.push((key, value)),
+        }
+    }
+    if !cfg!(feature="preserve_order") {
+        ordered.sort_by_key(|entry| entry.0);
+    }
+    ordered
+}
+
+/// Serializes a constant with its object keys ordered by this crate's
+/// `preserve_order` feature rather than by `serde_json::Map`, whose
+/// order follows serde_json's own feature of the same name.
+#[cfg(feature="with-serde")]
+struct Ordered<'a>(&'a Constant);
+
+#[cfg(feature="with-serde")]
+impl<'a> ::serde::Serialize for Ordered<'a> {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{SerializeMap, SerializeSeq};
+
+        match *self.0 {
+            Constant::Null => s.serialize_unit(),
+            Constant::Bool(b) => s.serialize_bool(b),
+            Constant::I64(n) => s.serialize_i64(n),
+            Constant::U64(n) => s.serialize_u64(n),
+            Constant::F64(x) => s.serialize_f64(x),
+            Constant::String(ref v) => s.serialize_str(v),
+            Constant::Array(ref elems) => {
+                let mut seq = s.serialize_seq(Some(elems.len()))?;
+                for elem in elems {
+                    seq.serialize_element(&Ordered(elem))?;
+                }
+                seq.end()
+            }
+            Constant::Object(ref entries) => {
+                let entries = ordered_entries(entries);
+                let mut map = s.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, &Ordered(value))?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(feature="with-serde")]
+impl Serializer for backend::SerdeJson {
+    fn serialize(&self, value: &Constant) -> String {
+        ::serde_json::to_string(&Ordered(value)).unwrap()
+    }
+
+    fn serialize_pretty(&self, value: &Constant) -> String {
+        ::serde_json::to_string_pretty(&Ordered(value)).unwrap()
+    }
+
+    fn new_buffer(&self, capacity: usize) -> TokenStream {
+        quote_expr!(::std::vec::Vec::<u8>::with_capacity(#capacity))
+    }
+
+    fn write_str(&self, buf: &TokenStream, s: TokenStream) -> TokenStream {
+        quote_expr!(#buf.extend_from_slice((#s).as_bytes());)
+    }
+
+    fn write_value(&self, buf: &TokenStream, expr: TokenStream) -> TokenStream {
+        quote_expr!({
+            ::serde_json::to_writer(&mut #buf, &(#expr))
+                .expect("json_macros: failed to serialize a spliced value");
+        })
+    }
+
+    fn pretty_text(&self, expr: TokenStream) -> TokenStream {
+        quote_expr!({
+            ::serde_json::to_string_pretty(&(#expr))
+                .expect("json_macros: failed to serialize a spliced value")
+        })
+    }
+
+    fn fmt_value(&self, f: &TokenStream, value: TokenStream) -> TokenStream {
+        quote_expr!({
+            // Forwards the text `to_writer` writes, which it only ever
+            // splits between characters, to a `fmt::Formatter`.
+            struct Adapter<'a, 'b: 'a>(&'a mut ::std::fmt::Formatter<'b>);
+
+            impl<'a, 'b> ::std::io::Write for Adapter<'a, 'b> {
+                fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+                    let written = ::std::str::from_utf8(buf).ok()
+                        .and_then(|s| self.0.write_str(s).ok());
+                    match written {
+                        ::std::option::Option::Some(()) => ::std::result::Result::Ok(buf.len()),
+                        ::std::option::Option::None => {
+                            ::std::result::Result::Err(::std::io::Error::new(
+                                ::std::io::ErrorKind::Other, "formatter error"))
+                        }
+                    }
+                }
+
+                fn flush(&mut self) -> ::std::io::Result<()> {
+                    ::std::result::Result::Ok(())
+                }
+            }
+
+            match ::serde_json::to_writer(&mut Adapter(#f), #value) {
+                ::std::result::Result::Ok(()) => ::std::result::Result::Ok(()),
+                ::std::result::Result::Err(ref e) if e.is_io() => {
+                    ::std::result::Result::Err(::std::fmt::Error)
+                }
+                ::std::result::Result::Err(_) => {
+                    panic!("json_macros: failed to serialize a spliced value")
+                }
+            }
+        })
+    }
+
+    fn write_buffer(&self, buf: &TokenStream, other: TokenStream) -> TokenStream {
+        quote_expr!(#buf.extend_from_slice(&(#other));)
+    }
+
+    fn buffer_str(&self, buf: TokenStream) -> TokenStream {
+        quote_expr!(::std::str::from_utf8(#buf).unwrap())
+    }
+
+    fn finish(&self, buf: TokenStream) -> TokenStream {
+        quote_expr!(::std::string::String::from_utf8(#buf).unwrap())
+    }
+}
@@ -0,0 +1,42 @@
+use std::error;
+use std::fmt;
+
+/// The error `try_json!` evaluates to when a spliced expression fails
+/// to convert into a value, such as a map whose keys do not serialize
+/// as strings.
+#[derive(Debug)]
+pub struct Error {
+    path: String,
+    cause: Box<dyn error::Error + Send + Sync>,
+}
+
+impl Error {
+    /// Used by the code `try_json!` expands to.
+    #[doc(hidden)]
+    pub fn new<E>(path: String, cause: E) -> Error
+        where E: Into<Box<dyn error::Error + Send + Sync>>
+    {
+        Error {
+            path,
+            cause: cause.into(),
+        }
+    }
+
+    /// Where the value that failed to convert would have gone, as a
+    /// JSONPath such as `$.items[3].id`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot convert the value at {}: {}", self.path, self.cause)
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.cause)
+    }
+}
@@ -1,19 +1,39 @@
-#![feature(plugin_registrar, quote)]
-#![feature(rustc_private)]
-
-extern crate rustc;
-extern crate rustc_plugin;
-extern crate syntax;
 #[cfg(feature="with-rustc-serialize")]
 extern crate rustc_serialize;
 #[cfg(feature="with-serde")]
 extern crate serde_json;
+#[cfg(feature="with-yaml")]
+extern crate serde_yaml;
+#[cfg(feature="with-toml")]
+extern crate toml;
+#[cfg(feature="with-cbor")]
+extern crate ciborium;
+#[cfg(feature="with-msgpack")]
+extern crate rmpv;
+
+extern crate json_macros_proc;
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+pub use json_macros_proc::{json, json_pretty, json_static, json_str, json_write, try_json};
+#[cfg(feature="with-rustc-serialize")]
+pub use json_macros_proc::rustc_json;
+#[cfg(feature="with-serde")]
+pub use json_macros_proc::serde_json;
+#[cfg(feature="with-yaml")]
+pub use json_macros_proc::yaml;
+#[cfg(feature="with-toml")]
+pub use json_macros_proc::toml;
+#[cfg(feature="with-cbor")]
+pub use json_macros_proc::cbor;
+#[cfg(feature="with-msgpack")]
+pub use json_macros_proc::msgpack;
 
-use rustc_plugin::Registry;
+// `rustc_serialize::json::Json` stores objects in a `BTreeMap`, which
+// sorts its keys whatever order the generated code inserts them in.
+#[cfg(all(feature="preserve_order", feature="with-rustc-serialize"))]
+compile_error!("the `preserve_order` feature of json_macros is not supported by the \
+                rustc-serialize backend");
 
-mod plugin;
+pub use error::Error;
 
-#[plugin_registrar]
-pub fn plugin_registrar(reg: &mut Registry) {
-    reg.register_macro("json", plugin::expand);
-}
+mod error;
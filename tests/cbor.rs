@@ -0,0 +1,96 @@
+#[macro_use]
+extern crate json_macros;
+extern crate ciborium;
+
+use std::collections::BTreeMap;
+
+use ciborium::Value;
+
+fn text(s: &str) -> Value {
+    Value::Text(s.to_string())
+}
+
+fn map(entries: &[(&str, Value)]) -> Value {
+    Value::Map(entries.iter().map(|&(k, ref v)| (text(k), v.clone())).collect())
+}
+
+fn encode(value: &Value) -> Vec<u8> {
+    let mut buf = vec![];
+    ciborium::into_writer(value, &mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn test_scalar_lits() {
+    assert_eq!(cbor!("foo"), text("foo"));
+    assert_eq!(cbor!(1234), Value::Integer(1234.into()));
+    assert_eq!(cbor!(-1234), Value::Integer((-1234).into()));
+    assert_eq!(cbor!(18446744073709551615u64), Value::Integer(u64::MAX.into()));
+    assert_eq!(cbor!(-12345.6), Value::Float(-12345.6));
+    assert_eq!(cbor!(null), Value::Null);
+    assert_eq!(cbor!(true), Value::Bool(true));
+}
+
+#[test]
+fn test_byte_strings() {
+    assert_eq!(cbor!(b"\x00\xffab"), Value::Bytes(vec![0, 0xff, b'a', b'b']));
+    assert_eq!(cbor!({ "raw": b"" }), map(&[("raw", Value::Bytes(vec![]))]));
+    // Spliced bytes are converted like any other value.
+    let bytes = b"ab";
+    assert_eq!(cbor!(bytes), Value::Array(vec![Value::Integer(97.into()),
+                                                Value::Integer(98.into())]));
+}
+
+#[test]
+fn test_compact_encoding() {
+    assert_eq!(encode(&cbor!(10)), [0x0a]);
+    assert_eq!(encode(&cbor!(-500)), [0x39, 0x01, 0xf3]);
+    assert_eq!(encode(&cbor!(1.5)), [0xf9, 0x3e, 0x00]);
+    assert_eq!(encode(&cbor!(0.1)), [0xfb, 0x3f, 0xb9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a]);
+    assert_eq!(encode(&cbor!(b"ab")), [0x42, b'a', b'b']);
+}
+
+#[test]
+fn test_objects() {
+    let id = 42;
+    assert_eq!(cbor!({ "b": [1, "x"], (id): true, name: null }),
+               map(&[("b", Value::Array(vec![Value::Integer(1.into()), text("x")])),
+                     ("42", Value::Bool(true)),
+                     ("name", Value::Null)]));
+}
+
+#[test]
+fn test_spreads_and_overrides() {
+    let base = cbor!({ "a": 1, "b": 2 });
+    // Keys keep the position they were first inserted at.
+    assert_eq!(cbor!({ ..base.clone(), "a": 3, "c": 4 }),
+               map(&[("a", Value::Integer(3.into())),
+                     ("b", Value::Integer(2.into())),
+                     ("c", Value::Integer(4.into()))]));
+    let debug = true;
+    assert_eq!(cbor!({ "level": "info", "level": "debug" if debug }),
+               map(&[("level", text("debug"))]));
+    // Keys other than text are only equal to keys of the same kind.
+    let one = |v: &str| Value::Map(vec![(Value::Integer(1.into()), text(v))]);
+    assert_eq!(cbor!({ ..one("one"), "1": 2, ..one("uno") }),
+               Value::Map(vec![(Value::Integer(1.into()), text("uno")),
+                               (text("1"), Value::Integer(2.into()))]));
+}
+
+#[test]
+#[should_panic(expected = "cbor!: `..` expects an object value")]
+fn test_object_spread_non_object() {
+    let base = cbor!([1, 2]);
+    cbor!({ ..base });
+}
+
+#[test]
+fn test_splices() {
+    let mut counts = BTreeMap::new();
+    counts.insert("a", 1);
+    let none: Option<u8> = None;
+    assert_eq!(cbor!({ "counts": counts, "nothing"?: none, for i in 0..2 => (i): [..0..i] }),
+               map(&[("counts", map(&[("a", Value::Integer(1.into()))])),
+                     ("0", Value::Array(vec![])),
+                     ("1", Value::Array(vec![Value::Integer(0.into())]))]));
+}
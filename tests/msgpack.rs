@@ -0,0 +1,79 @@
+#[macro_use]
+extern crate json_macros;
+extern crate rmpv;
+
+use std::collections::BTreeMap;
+
+use rmpv::Value;
+
+fn map(entries: &[(&str, Value)]) -> Value {
+    Value::Map(entries.iter().map(|&(k, ref v)| (Value::from(k), v.clone())).collect())
+}
+
+fn encode(value: &Value) -> Vec<u8> {
+    let mut buf = vec![];
+    rmpv::encode::write_value(&mut buf, value).unwrap();
+    buf
+}
+
+#[test]
+fn test_scalar_lits() {
+    assert_eq!(msgpack!("foo"), Value::from("foo"));
+    assert_eq!(msgpack!(1234), Value::from(1234));
+    assert_eq!(msgpack!(-1234), Value::from(-1234));
+    assert_eq!(msgpack!(18446744073709551615u64), Value::from(u64::MAX));
+    assert_eq!(msgpack!(null), Value::Nil);
+    assert_eq!(msgpack!(true), Value::Boolean(true));
+}
+
+#[test]
+fn test_float_lits() {
+    assert_eq!(msgpack!(1.5), Value::F32(1.5));
+    assert_eq!(msgpack!(-2.0), Value::F32(-2.0));
+    assert_eq!(msgpack!(0.1), Value::F64(0.1));
+    assert_eq!(msgpack!(0.1f32), Value::F32(0.1));
+    // Spliced floats keep the width of their type.
+    let x = 1.5;
+    assert_eq!(msgpack!(x), Value::F64(1.5));
+}
+
+#[test]
+fn test_byte_strings() {
+    assert_eq!(msgpack!(b"\x00\xffab"), Value::Binary(vec![0, 0xff, b'a', b'b']));
+    assert_eq!(msgpack!([b"", 1]), Value::Array(vec![Value::Binary(vec![]), Value::from(1)]));
+}
+
+#[test]
+fn test_compact_encoding() {
+    assert_eq!(encode(&msgpack!(10)), [0x0a]);
+    assert_eq!(encode(&msgpack!(-500)), [0xd1, 0xfe, 0x0c]);
+    assert_eq!(encode(&msgpack!(1.5)), [0xca, 0x3f, 0xc0, 0x00, 0x00]);
+    assert_eq!(encode(&msgpack!(b"ab")), [0xc4, 0x02, b'a', b'b']);
+    assert_eq!(encode(&msgpack!({ "a": [] })), [0x81, 0xa1, b'a', 0x90]);
+}
+
+#[test]
+fn test_spreads_and_overrides() {
+    let base = msgpack!({ "a": 1, "b": 2 });
+    // Keys keep the position they were first inserted at.
+    assert_eq!(msgpack!({ ..base.clone(), "a": 3, "c": 4 }),
+               map(&[("a", Value::from(3)), ("b", Value::from(2)), ("c", Value::from(4))]));
+    let id = 7;
+    assert_eq!(msgpack!({ (id): "x", "7": "y" if id > 0 }), map(&[("7", Value::from("y"))]));
+    // Keys other than strings are only equal to keys of the same kind.
+    let one = |v: &str| Value::Map(vec![(Value::from(1), Value::from(v))]);
+    assert_eq!(msgpack!({ ..one("one"), "1": 2, ..one("uno") }),
+               Value::Map(vec![(Value::from(1), Value::from("uno")),
+                               (Value::from("1"), Value::from(2))]));
+}
+
+#[test]
+fn test_splices() {
+    let mut counts = BTreeMap::new();
+    counts.insert("a", 1);
+    let some = Some("x");
+    assert_eq!(msgpack!({ "counts": counts, "some"?: some, "list": [for i in 0..2 => i] }),
+               map(&[("counts", map(&[("a", Value::from(1))])),
+                     ("some", Value::from("x")),
+                     ("list", Value::Array(vec![Value::from(0), Value::from(1)]))]));
+}
@@ -1,28 +1,64 @@
-#![feature(plugin)]
-#![plugin(json_macros)]
+// Only the JSON backends provide `json!`.
+#![cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
 
-use std::collections::BTreeMap;
+#[macro_use]
+extern crate json_macros;
 
 #[cfg(feature="with-serde")]
 extern crate serde_json;
 #[cfg(feature="with-rustc-serialize")]
 extern crate rustc_serialize;
 
-#[cfg(feature="with-serde")]
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
 mod imports {
-    pub use serde_json::value::Value;
-    use serde_json::value::Serializer;
+    pub use serde_json::{Map, Value};
     extern crate serde;
-    use self::serde::ser::Serialize;
+    use self::serde::Serialize;
 
     // convenience fn to avoid re-writing tests, close to serde_json's
     // to_value function.
-    // (when https://github.com/serde-rs/json/pull/52 lands this can
-    // just re-export serde_json::value:;to_value)
+    #[allow(clippy::multiple_bound_locations)]
     pub fn to_value<T: ?Sized>(value: &T) -> Value where T: Serialize {
-        let mut ser = Serializer::new();
-        value.serialize(&mut ser).ok().unwrap();
-        ser.unwrap()
+        ::serde_json::to_value(value).unwrap()
+    }
+
+    // The text macros sort keys unless json_macros' own `preserve_order`
+    // is enabled, even if another crate enables serde_json's.
+    fn text_order(value: &Value) -> Value {
+        let mut value = value.clone();
+        if !cfg!(feature="preserve_order") {
+            value.sort_all_objects();
+        }
+        value
+    }
+
+    pub fn to_string(value: &Value) -> String {
+        ::serde_json::to_string(&text_order(value)).unwrap()
+    }
+
+    pub fn to_pretty_string(value: &Value) -> String {
+        ::serde_json::to_string_pretty(&text_order(value)).unwrap()
+    }
+
+    // convenience renaming for rough rustc-serialize compatibility
+    pub trait Compat {
+        fn as_string(&self) -> Option<&str>;
+        fn as_boolean(&self) -> Option<bool>;
+        fn find(&self, key: &str) -> Option<&Value>;
+    }
+
+    impl Compat for Value {
+        fn as_string(&self) -> Option<&str> {
+            self.as_str()
+        }
+
+        fn as_boolean(&self) -> Option<bool> {
+            self.as_bool()
+        }
+
+        fn find(&self, key: &str) -> Option<&Value> {
+            self.get(key)
+        }
     }
 }
 
@@ -31,12 +67,22 @@ mod imports {
     pub use rustc_serialize::json::ToJson;
     // convenience renaming for rough serde compatibility
     pub use rustc_serialize::json::Json as Value;
+    pub use rustc_serialize::json::Object as Map;
 
     // convenience fn to avoid re-writing tests, close to serde_json's
     // to_value function.
+    #[allow(clippy::multiple_bound_locations)]
     pub fn to_value<T: ?Sized>(value: &T) -> Value where T: ToJson {
         value.to_json()
     }
+
+    pub fn to_string(value: &Value) -> String {
+        value.to_string()
+    }
+
+    pub fn to_pretty_string(value: &Value) -> String {
+        value.pretty().to_string()
+    }
 }
 
 use imports::*;
@@ -81,14 +127,14 @@ fn test_array_lit() {
 
 #[test]
 fn test_object_lit() {
-    let empty = BTreeMap::new();
+    let empty = Map::new();
     assert_eq!(json!({}), Value::Object(empty));
 
-    let mut foo_bar = BTreeMap::new();
+    let mut foo_bar = Map::new();
     foo_bar.insert("foo".to_string(), json!("bar"));
     assert_eq!(json!({"foo": "bar"}), Value::Object(foo_bar));
 
-    let mut foo_bar_baz_123 = BTreeMap::new();
+    let mut foo_bar_baz_123 = Map::new();
     foo_bar_baz_123.insert("foo".to_string(), json!("bar"));
     foo_bar_baz_123.insert("baz".to_string(), json!(123));
     assert_eq!(json!({
@@ -96,8 +142,8 @@ fn test_object_lit() {
         "baz": 123
     }), Value::Object(foo_bar_baz_123));
 
-    let mut nested = BTreeMap::new();
-    let mut bar_baz = BTreeMap::new();
+    let mut nested = Map::new();
+    let mut bar_baz = Map::new();
     bar_baz.insert("bar".to_string(), json!("baz"));
     nested.insert("foo".to_string(), Value::Object(bar_baz));
     nested.insert("quux".to_string(), Value::Null);
@@ -107,6 +153,26 @@ fn test_object_lit() {
     }), Value::Object(nested));
 }
 
+#[test]
+fn test_ident_keys() {
+    let mut expected = Map::new();
+    expected.insert("id".to_string(), json!(1));
+    expected.insert("name".to_string(), json!("x"));
+    expected.insert("type".to_string(), json!(null));
+    assert_eq!(json!({ id: 1, "name": "x", type: null }), Value::Object(expected));
+}
+
+#[test]
+fn test_computed_keys() {
+    let id = 42;
+    let name = String::from("name");
+    let mut expected = Map::new();
+    expected.insert("42".to_string(), json!(true));
+    expected.insert("name".to_string(), json!("x"));
+    expected.insert("fixed".to_string(), json!(null));
+    assert_eq!(json!({ (id): true, (name): "x", fixed: null }), Value::Object(expected));
+}
+
 #[test]
 fn test_expr_insertion() {
     let hello = "hello world!";
@@ -116,3 +182,458 @@ fn test_expr_insertion() {
     assert_eq!(json.find("message").and_then(|j| j.as_string()),
                Some(hello));
 }
+
+#[cfg(all(feature="with-serde", feature="with-rustc-serialize"))]
+#[test]
+fn test_both_backends() {
+    let rustc: rustc_serialize::json::Json = rustc_json!({
+        "foo": [1, null, "bar"],
+        "baz": (true)
+    });
+    let serde: serde_json::Value = serde_json!({
+        "foo": [1, null, "bar"],
+        "baz": (true)
+    });
+    assert_eq!(rustc.to_string(), serde_json::to_string(&serde).unwrap());
+    assert_eq!(json!("quux"), rustc_json!("quux"));
+}
+
+#[test]
+fn test_bare_expr_insertion() {
+    struct User { id: i32, name: &'static str }
+    let user = User { id: 7, name: "ferris" };
+    let tags = ["a", "b"];
+    let json = json!({
+        "id": user.id,
+        "name": user.name.to_uppercase(),
+        "first_tag": tags[0],
+        "count": tags.len() as i64 + 1,
+        "pi": ::std::f64::consts::PI,
+        "paren": (user.id)
+    });
+    assert_eq!(json.find("id").and_then(|j| j.as_i64()), Some(7));
+    assert_eq!(json.find("name").and_then(|j| j.as_string()), Some("FERRIS"));
+    assert_eq!(json.find("first_tag").and_then(|j| j.as_string()), Some("a"));
+    assert_eq!(json.find("count").and_then(|j| j.as_i64()), Some(3));
+    assert_eq!(json.find("pi").and_then(|j| j.as_f64()), Some(::std::f64::consts::PI));
+    assert_eq!(json.find("paren").and_then(|j| j.as_i64()), Some(7));
+    assert_eq!(json!([user.id, -user.id]), json!([7, -7]));
+}
+
+#[test]
+fn test_object_spread() {
+    let base = json!({ "a": 1, "b": 2 });
+    let mut expected = Map::new();
+    expected.insert("a".to_string(), json!(1));
+    expected.insert("b".to_string(), json!(3));
+    expected.insert("c".to_string(), json!(4));
+    assert_eq!(json!({ ..base.clone(), "b": 3, "c": 4 }), Value::Object(expected));
+    assert_eq!(json!({ "b": 3, ..base.clone() }), base);
+}
+
+#[test]
+#[should_panic(expected = "json!: `..` expects an object value")]
+fn test_object_spread_non_object() {
+    let base = json!([1, 2]);
+    json!({ ..base });
+}
+
+#[test]
+fn test_array_spread() {
+    let rest = vec![3, 4];
+    assert_eq!(json!([1, 2, ..rest.iter(), 5]), json!([1, 2, 3, 4, 5]));
+    assert_eq!(json!([..rest]), json!([3, 4]));
+    assert_eq!(json!([..Vec::<i32>::new()]), json!([]));
+    let words = ["a", "b"];
+    assert_eq!(json!([..words.iter().map(|w| json!({ "word": w }))]),
+               json!([{ "word": "a" }, { "word": "b" }]));
+}
+
+#[test]
+fn test_generated_bindings_are_hygienic() {
+    let xs = vec![1, 2];
+    assert_eq!(json!([0, ..xs]), json!([0, 1, 2]));
+    let _ob = json!({ "a": 1 });
+    assert_eq!(json!({ .._ob, "b": 2 }), json!({ "a": 1, "b": 2 }));
+}
+
+#[test]
+fn test_optional_entries() {
+    let some = Some("ferris");
+    let none: Option<&str> = None;
+    let mut expected = Map::new();
+    expected.insert("nickname".to_string(), json!("ferris"));
+    expected.insert("name".to_string(), json!("x"));
+    assert_eq!(json!({ "nickname"?: some, "alias"?: none, name: "x" }),
+               Value::Object(expected));
+    assert_eq!(json!({ id?: (Some(1).map(|x| x + 1)) }), json!({ "id": 2 }));
+}
+
+#[test]
+fn test_guards() {
+    let info = "details";
+    for &verbose in &[true, false] {
+        let mut expected = Map::new();
+        expected.insert("name".to_string(), json!("x"));
+        if verbose {
+            expected.insert("debug".to_string(), json!("details"));
+        }
+        assert_eq!(json!({ "debug": (info) if verbose, "name": "x" }),
+                   Value::Object(expected));
+    }
+    let cond = false;
+    assert_eq!(json!([1, 2 if cond, 3 if !cond]), json!([1, 3]));
+    let rest = [4, 5];
+    assert_eq!(json!([..rest.iter() if rest.len() > 1]), json!([4, 5]));
+}
+
+#[test]
+fn test_array_comprehension() {
+    struct Item { id: i32, name: &'static str, tags: Vec<&'static str> }
+    let items = vec![
+        Item { id: 1, name: "one", tags: vec!["odd"] },
+        Item { id: 2, name: "two", tags: vec![] },
+    ];
+    assert_eq!(json!([for item in (&items) => { "id": (item.id), "name": (item.name) }]),
+               json!([{ "id": 1, "name": "one" }, { "id": 2, "name": "two" }]));
+    assert_eq!(json!([0, for item in &items => item.id, 3]), json!([0, 1, 2, 3]));
+    assert_eq!(json!([for item in &items => item.id if item.id > 1]), json!([2]));
+    assert_eq!(json!([for item in &items => { "tags": [for t in &item.tags => t] }]),
+               json!([{ "tags": ["odd"] }, { "tags": [] }]));
+    assert_eq!(json!([for item in &items => ..item.tags.iter()]), json!(["odd"]));
+    assert_eq!(json!([for (i, x) in vec!["a", "b"].into_iter().enumerate() => [i, x]]),
+               json!([[0usize, "a"], [1usize, "b"]]));
+}
+
+#[test]
+fn test_object_comprehension() {
+    use std::collections::BTreeMap;
+
+    let mut map = BTreeMap::new();
+    map.insert("a", 1);
+    map.insert("b", 2);
+    assert_eq!(json!({ for (k, v) in (&map) => (k): { "value": (v) } }),
+               json!({ "a": { "value": 1 }, "b": { "value": 2 } }));
+    assert_eq!(json!({ "total": 3, for (k, v) in &map => (k): v if *v > 1, "z": null }),
+               json!({ "total": 3, "b": 2, "z": null }));
+    assert_eq!(json!({ for (k, v) in &map => (k): [for i in 0..*v => i] }),
+               json!({ "a": [0], "b": [0, 1] }));
+    assert_eq!(json!({ for k in ["x", "y"] => for i in 0..2 => (format!("{}{}", k, i)): i }),
+               json!({ "x0": 0, "x1": 1, "y0": 0, "y1": 1 }));
+}
+
+#[test]
+fn test_guarded_duplicate_keys() {
+    // Only unconditional literal keys are checked for duplicates, so a
+    // guarded entry may still override an earlier one.
+    for &dev in &[true, false] {
+        let url = if dev { "localhost" } else { "example.com" };
+        assert_eq!(json!({ "url": "example.com", "url": "localhost" if dev }),
+                   json!({ "url": url }));
+    }
+}
+
+// Checks that `json_str!` writes exactly what `json!` serializes to.
+macro_rules! assert_json_str {
+    ($($json:tt)*) => {
+        assert_eq!(json_str!($($json)*), to_string(&json!($($json)*)))
+    }
+}
+
+#[test]
+fn test_json_str_constant() {
+    let s: &'static str = json_str!({ "b": [1, -2, 2.5, 1e20, null, true], "a": {} });
+    assert_eq!(s, to_string(&json!({ "b": [1, -2, 2.5, 1e20, null, true], "a": {} })));
+    assert_json_str!("esc\"aped\n\u{1}");
+    assert_json_str!([]);
+    assert_json_str!([[], {}, [{}]]);
+    assert_json_str!({ z: 1, "y": 2u64, x: -3i64, "w": 1.5f32 });
+}
+
+#[test]
+fn test_json_str_spliced() {
+    use std::collections::BTreeMap;
+
+    let x = 5;
+    let name = "ferris";
+    let tags = vec!["a", "b"];
+    let none: Option<i32> = None;
+    let mut map = BTreeMap::new();
+    map.insert("m", 1);
+    map.insert("b", 2);
+    let base = json!({ "base": true, "k": 0 });
+    assert_json_str!(x);
+    assert_json_str!([x, "lit", { "name": name }]);
+    assert_json_str!({ "z": x, "a": [x, ..tags.iter(), 3 if x > 1], "n"?: none, "s"?: Some(1) });
+    assert_json_str!({ "k": 1, for (k, v) in &map => (k): [v], ..base.clone(), "z": 2 if x > 1 });
+    assert_json_str!({ "k": 1, "k": 2 if x > 1, "k": 3 if x > 9 });
+    assert_json_str!([for t in &tags => { "tag": t, "len": t.len() }, 1 if x > 9]);
+    assert_json_str!({ "empty": [1 if x > 9], "nested": { "deep": [[x]] } });
+}
+
+#[test]
+#[should_panic(expected = "json_str!: `..` expects an object value")]
+fn test_json_str_spread_non_object() {
+    let base = json!([1, 2]);
+    json_str!({ ..base });
+}
+
+// Checks that `json_pretty!` writes exactly what `json!` pretty-prints to.
+macro_rules! assert_json_pretty {
+    ($($json:tt)*) => {
+        assert_eq!(json_pretty!($($json)*), to_pretty_string(&json!($($json)*)))
+    }
+}
+
+#[test]
+fn test_json_pretty_constant() {
+    let s: &'static str = json_pretty!({ "b": [1, [2, {}], { "c": null }], "a": [] });
+    #[cfg(not(feature="preserve_order"))]
+    assert_eq!(s, "{\n  \"a\": [],\n  \"b\": [\n    1,\n    [\n      2,\n      {}\n    ],\n    \
+                   {\n      \"c\": null\n    }\n  ]\n}");
+    #[cfg(feature="preserve_order")]
+    assert_eq!(s, "{\n  \"b\": [\n    1,\n    [\n      2,\n      {}\n    ],\n    {\n      \"c\": \
+                   null\n    }\n  ],\n  \"a\": []\n}");
+    assert_json_pretty!("multi\nline");
+    assert_json_pretty!([]);
+    assert_json_pretty!([[], {}, [{ "x": [1.5] }]]);
+}
+
+#[test]
+fn test_json_pretty_spliced() {
+    let x = 5;
+    let tags = vec!["a", "b"];
+    let none: Option<i32> = None;
+    let nested = json!({ "deep": [[1, {}], { "k": [] }] });
+    assert_json_pretty!(nested);
+    assert_json_pretty!([x, { "v": nested }, [nested]]);
+    assert_json_pretty!({ "z": x, "a": [x, ..tags.iter(), 3 if x > 1], "n"?: none, "s"?: Some(&nested) });
+    assert_json_pretty!({ for t in &tags => (t): { "nested": nested }, "empty": [1 if x > 9] });
+    assert_json_pretty!([for t in &tags => [t, nested], {} if x > 9]);
+    assert_json_pretty!({ "skipped": 1 if x > 9 });
+}
+
+#[cfg(feature="with-rustc-serialize")]
+#[test]
+fn test_json_pretty_indent() {
+    use rustc_serialize::json::as_pretty_json;
+
+    let x = json!({ "deep": [[1, {}], { "k": [] }] });
+    assert_eq!(json_pretty!(indent = 4, { "a": [1, { "b": x }] }),
+               as_pretty_json(&json!({ "a": [1, { "b": x }] })).indent(4).to_string());
+    assert_eq!(json_pretty!(indent = 0, [x, [1]]),
+               as_pretty_json(&json!([x, [1]])).indent(0).to_string());
+    let s: &'static str = json_pretty!(indent = 3, { "a": [1] });
+    assert_eq!(s, as_pretty_json(&json!({ "a": [1] })).indent(3).to_string());
+}
+
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[test]
+fn test_json_pretty_indent() {
+    extern crate serde;
+    use serde_json::ser::{PrettyFormatter, Serializer};
+
+    fn pretty(value: &Value, indent: &[u8]) -> String {
+        let mut out = Vec::new();
+        let mut ser = Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(indent));
+        serde::Serialize::serialize(value, &mut ser).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    let x = json!({ "deep": [[1, {}], { "k": [] }] });
+    assert_eq!(json_pretty!(indent = 4, { "a": [1, { "b": x }] }),
+               pretty(&json!({ "a": [1, { "b": x }] }), b"    "));
+    assert_eq!(json_pretty!(indent = 0, [x, [1]]), pretty(&json!([x, [1]]), b""));
+    let s: &'static str = json_pretty!(indent = 3, { "a": [1] });
+    assert_eq!(s, pretty(&json!({ "a": [1] }), b"   "));
+}
+
+// Checks that `json_write!` writes exactly what `json!` serializes to,
+// both to an `io::Write` and to a `fmt::Write`.
+macro_rules! assert_json_write {
+    ($($json:tt)*) => {{
+        let mut bytes = Vec::new();
+        json_write!(&mut bytes, $($json)*).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), to_string(&json!($($json)*)));
+        let mut s = String::new();
+        json_write!(&mut s, $($json)*).unwrap();
+        assert_eq!(s, to_string(&json!($($json)*)));
+    }}
+}
+
+#[test]
+fn test_json_write() {
+    let x = 5;
+    let tags = vec!["a".to_string(), "b\"c".to_string()];
+    assert_json_write!({ "b": [1, -2, 2.5, null], "a": {} });
+    assert_json_write!([x, "lit", { "tags": tags }, ..tags.iter(), 3 if x > 1]);
+    assert_json_write!({ "k": 1, "n"?: Some(x), "a": [..tags.iter()] });
+}
+
+#[test]
+fn test_json_write_streams_entries() {
+    use std::collections::BTreeMap;
+
+    let x = 5;
+    let mut map = BTreeMap::new();
+    map.insert("m", 1);
+    let base = json!({ "base": [true], "k": 0 });
+    // Objects whose keys are only known at runtime are written in the
+    // order their entries are produced, repeated keys included.
+    let mut s = String::new();
+    json_write!(&mut s, { "k": 1, for (k, v) in &map => (k): [v], ..base, "n"?: Some(x) })
+        .unwrap();
+    assert_eq!(s, r#"{"k":1,"m":[1],"base":[true],"k":0,"n":5}"#);
+    let mut s = String::new();
+    json_write!(&mut s, { "z": 1, "a": 2 if x > 1, "a": 3 if x > 9, (x): null }).unwrap();
+    assert_eq!(s, r#"{"z":1,"a":2,"5":null}"#);
+    let mut s = String::new();
+    json_write!(&mut s, { for i in 0..x => (i): i if i > 9 }).unwrap();
+    assert_eq!(s, "{}");
+}
+
+#[test]
+fn test_json_write_reborrow() {
+    use std::fmt;
+    use std::io::{self, Write};
+
+    fn write_all<W: Write>(w: &mut W, x: i32) -> io::Result<()> {
+        json_write!(w, { "x": x })?;
+        json_write!(w, [x])
+    }
+
+    struct Point(i32, i32);
+
+    impl fmt::Display for Point {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            json_write!(f, { "x": (self.0), "y": (self.1) }).map_err(|_| fmt::Error)
+        }
+    }
+
+    let mut out = Vec::new();
+    write_all(&mut out, 3).unwrap();
+    assert_eq!(out, b"{\"x\":3}[3]");
+    assert_eq!(Point(1, 2).to_string(), "{\"x\":1,\"y\":2}");
+
+    let mut buf = [0u8; 4];
+    let mut short = &mut buf[..];
+    let err = json_write!(&mut short, [1, (2), 3]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+}
+
+#[test]
+fn test_hoisted_constants() {
+    let mut seen = vec![];
+    for i in 0..3 {
+        let mut value = json!({ "i": i, "config": { "retries": [1, 2.5], "debug": null } });
+        assert_eq!(value, json!({ "i": i, "config": { "retries": [1, (2.5)], "debug": null } }));
+        // Each use gets a copy of its own.
+        value.as_object_mut().unwrap().get_mut("config").unwrap()
+             .as_object_mut().unwrap().insert("changed".to_string(), json!(true));
+        seen.push(value);
+    }
+    assert_eq!(seen[2], json!({ "i": 2, "config": { "retries": [1, 2.5], "debug": null,
+                                                    "changed": true } }));
+}
+
+#[test]
+fn test_json_static() {
+    fn config() -> &'static Value {
+        json_static!({ "retries": [1, 2], "name": "x", "debug": null })
+    }
+
+    assert_eq!(*config(), json!({ "retries": [1, 2], "name": "x", "debug": null }));
+    assert!(std::ptr::eq(config(), config()));
+    assert_eq!(*json_static!("scalar"), json!("scalar"));
+}
+
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[test]
+#[should_panic(expected = "json!: cannot convert the value at $.items[1][\"my id\"][0]")]
+fn test_conversion_failure_path() {
+    use std::collections::BTreeMap;
+
+    // serde_json only accepts maps whose keys serialize as strings.
+    let mut bad = BTreeMap::new();
+    bad.insert(vec![1], 2);
+    let key = "my id";
+    json!({ "items": [0, { (key): [bad] }] });
+}
+
+#[cfg(feature="with-serde")]
+#[test]
+#[should_panic(expected = "serde_json!: cannot convert the value at $[0]")]
+fn test_conversion_failure_name() {
+    use std::collections::BTreeMap;
+
+    let mut bad = BTreeMap::new();
+    bad.insert(vec![1], 2);
+    serde_json!([bad]);
+}
+
+#[cfg(feature="preserve_order")]
+#[test]
+fn test_preserve_order() {
+    fn keys(value: &Value) -> Vec<&str> {
+        value.as_object().unwrap().keys().map(|k| &k[..]).collect()
+    }
+
+    let x = 1;
+    assert_eq!(keys(&json!({ "b": 1, "a": x, "c": { "z": null, "y": [] } })), ["b", "a", "c"]);
+    assert_eq!(json_str!({ "b": 1, "a": [], "c": { "z": null, "y": [] } }),
+               r#"{"b":1,"a":[],"c":{"z":null,"y":[]}}"#);
+    assert_json_str!({ "b": 1, "a": x, "b": 2 if x > 0, ("c"): 3, "a": 4 if x > 9 });
+    assert_json_pretty!({ "b": [1, { "z": x, "y": null }], "a": [] });
+}
+
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[test]
+fn test_text_key_order() {
+    // Constant and spliced objects in one text follow json_macros' own
+    // `preserve_order`, whether or not serde_json's is enabled.
+    let x = 1;
+    let s = json_str!({ "b": { "d": 1, "c": 2 }, "a": { "f": x, "e": (x) } });
+    #[cfg(not(feature="preserve_order"))]
+    assert_eq!(s, r#"{"a":{"e":1,"f":1},"b":{"c":2,"d":1}}"#);
+    #[cfg(feature="preserve_order")]
+    assert_eq!(s, r#"{"b":{"d":1,"c":2},"a":{"f":1,"e":1}}"#);
+    let s: &'static str = json_pretty!({ "b": 1, "a": { "d": 1, "c": 2 } });
+    #[cfg(not(feature="preserve_order"))]
+    assert_eq!(s, "{\n  \"a\": {\n    \"c\": 2,\n    \"d\": 1\n  },\n  \"b\": 1\n}");
+    #[cfg(feature="preserve_order")]
+    assert_eq!(s, "{\n  \"b\": 1,\n  \"a\": {\n    \"d\": 1,\n    \"c\": 2\n  }\n}");
+}
+
+#[test]
+fn test_try_json() {
+    let x = 1;
+    let value: Result<Value, json_macros::Error> = try_json!({ "a": [x, 2.5], "b": { "c": null } });
+    assert_eq!(value.unwrap(), json!({ "a": [1, 2.5], "b": { "c": null } }));
+    assert_eq!(try_json!([for i in 0..3 => i if i != x]).unwrap(), json!([0, 2]));
+
+    let base = json!([1]);
+    let err = try_json!({ "a": [{ "b": { ..base } }] }).unwrap_err();
+    assert_eq!(err.path(), "$.a[0].b");
+    assert_eq!(err.to_string(),
+               "cannot convert the value at $.a[0].b: `..` expects an object value");
+}
+
+#[cfg(all(feature="with-serde", not(feature="with-rustc-serialize")))]
+#[test]
+fn test_try_json_error() {
+    use std::collections::BTreeMap;
+    use std::error::Error;
+
+    let mut bad = BTreeMap::new();
+    bad.insert(vec![1], 2);
+    let err = try_json!({ "items": [1, 2, 3, { "id": (bad.clone()) }, 5] }).unwrap_err();
+    assert_eq!(err.path(), "$.items[3].id");
+    assert_eq!(err.to_string(), "cannot convert the value at $.items[3].id: key must be a string");
+    assert!(err.source().is_some());
+
+    let mut maps = BTreeMap::new();
+    maps.insert("fine", BTreeMap::new());
+    maps.insert("not fine", bad);
+    let err = try_json!({ "ok": true, for (k, v) in &maps => (k): [..v.values(), v] }).unwrap_err();
+    assert_eq!(err.path(), "$[\"not fine\"][1]");
+}
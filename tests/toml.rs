@@ -0,0 +1,86 @@
+#[macro_use]
+extern crate json_macros;
+extern crate toml;
+
+use std::collections::BTreeMap;
+
+use toml::Value;
+use toml::map::Map;
+
+fn table(entries: &[(&str, Value)]) -> Value {
+    let mut map = Map::new();
+    for &(k, ref v) in entries {
+        map.insert(k.to_string(), v.clone());
+    }
+    Value::Table(map)
+}
+
+#[test]
+fn test_scalar_lits() {
+    assert_eq!(toml!("foo").as_str(), Some("foo"));
+    assert_eq!(toml!(1234).as_integer(), Some(1234));
+    assert_eq!(toml!(-1234).as_integer(), Some(-1234));
+    assert_eq!(toml!(-12345.6).as_float(), Some(-12345.6));
+    assert_eq!(toml!(true).as_bool(), Some(true));
+}
+
+#[test]
+fn test_tables_and_arrays() {
+    assert_eq!(toml!({}), Value::Table(Map::new()));
+    assert_eq!(toml!([]), Value::Array(vec![]));
+    let id = 42;
+    assert_eq!(toml!({ "server": { "ports": [80, 443] }, (id): true, name: "x" }),
+               table(&[("server", table(&[("ports", Value::Array(vec![Value::Integer(80),
+                                                                      Value::Integer(443)]))])),
+                       ("42", Value::Boolean(true)),
+                       ("name", Value::String("x".to_string()))]));
+    // Arrays of arrays and of tables may hold different arrays and tables.
+    assert_eq!(toml!([[1, 2], ["a"]]).as_array().map(|a| a.len()), Some(2));
+    assert_eq!(toml!([{ "a": 1 }, { "b": "c" }]).as_array().map(|a| a.len()), Some(2));
+}
+
+#[test]
+fn test_splices() {
+    let mut deps = BTreeMap::new();
+    deps.insert("serde", "1.0");
+    let name = "json_macros";
+    let optional: Option<&str> = None;
+    let manifest = toml!({
+        "package": { "name": name, "version": "0.3.0", "license"?: optional },
+        "dependencies": deps,
+        "features": { for f in ["a", "b"] => (f): [] }
+    });
+    assert_eq!(manifest["package"]["name"].as_str(), Some("json_macros"));
+    assert!(manifest["package"].get("license").is_none());
+    assert_eq!(manifest["dependencies"]["serde"].as_str(), Some("1.0"));
+    assert_eq!(manifest["features"]["b"], Value::Array(vec![]));
+    assert_eq!(toml!({ ..manifest.clone(), "dependencies": {} })["package"], manifest["package"]);
+}
+
+#[test]
+fn test_document() {
+    let port = 8080;
+    let doc = toml!({ "title": "web", "server": { "hosts": ["a", "b"], "port": port } });
+    assert_eq!(toml::to_string(&doc).unwrap(),
+               "title = \"web\"\n\n[server]\nhosts = [\"a\", \"b\"]\nport = 8080\n");
+}
+
+#[test]
+fn test_key_order() {
+    let doc = toml!({ "b": 1, "a": { "d": 2, "c": 3 } });
+    let keys: Vec<&str> = doc.as_table().unwrap().keys().map(|k| &k[..]).collect();
+    #[cfg(not(feature="preserve_order"))]
+    assert_eq!(keys, ["a", "b"]);
+    #[cfg(feature="preserve_order")]
+    assert_eq!(keys, ["b", "a"]);
+    #[cfg(feature="preserve_order")]
+    assert_eq!(toml::to_string(&doc).unwrap(), "b = 1\n\n[a]\nd = 2\nc = 3\n");
+}
+
+#[test]
+#[should_panic(expected = "toml!: cannot convert the value at $.package.license")]
+fn test_conversion_failure_path() {
+    // TOML has no null, so a `None` cannot be converted.
+    let license: Option<&str> = None;
+    toml!({ "package": { "name": "x", "license": license } });
+}
@@ -0,0 +1,13 @@
+extern crate trybuild;
+
+#[cfg(any(feature="with-rustc-serialize", feature="with-serde"))]
+#[test]
+fn test_json_errors() {
+    trybuild::TestCases::new().compile_fail("tests/ui/json/*.rs");
+}
+
+#[cfg(feature="with-toml")]
+#[test]
+fn test_toml_errors() {
+    trybuild::TestCases::new().compile_fail("tests/ui/toml/*.rs");
+}
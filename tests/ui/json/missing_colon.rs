@@ -0,0 +1,6 @@
+#[macro_use]
+extern crate json_macros;
+
+fn main() {
+    json!({ "a" 1 });
+}
@@ -0,0 +1,142 @@
+#[macro_use]
+extern crate json_macros;
+extern crate serde;
+extern crate serde_yaml;
+
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+
+fn to_value<T: ?Sized + Serialize>(value: &T) -> Value {
+    serde_yaml::to_value(value).unwrap()
+}
+
+fn mapping(entries: &[(&str, Value)]) -> Value {
+    let mut map = Mapping::new();
+    for &(k, ref v) in entries {
+        map.insert(Value::String(k.to_string()), v.clone());
+    }
+    Value::Mapping(map)
+}
+
+#[test]
+fn test_scalar_lits() {
+    assert_eq!(yaml!("foo").as_str(), Some("foo"));
+    assert_eq!(yaml!(1234).as_i64(), Some(1234));
+    assert_eq!(yaml!(-1234).as_i64(), Some(-1234));
+    assert_eq!(yaml!(-12345.6).as_f64(), Some(-12345.6));
+    assert!(yaml!(null).is_null());
+    assert_eq!(yaml!(true).as_bool(), Some(true));
+}
+
+#[test]
+fn test_array_lit() {
+    assert_eq!(yaml!([]), Value::Sequence(vec![]));
+    assert_eq!(yaml!([null]), Value::Sequence(vec![Value::Null]));
+    let foobar = Value::Sequence(vec![to_value("foo"),
+                                      Value::Sequence(vec![to_value("bar")]),
+                                      to_value("baz")]);
+    assert_eq!(yaml!(["foo", ["bar"], "baz"]), foobar);
+}
+
+#[test]
+fn test_object_lit() {
+    assert_eq!(yaml!({}), Value::Mapping(Mapping::new()));
+    assert_eq!(yaml!({ "foo": { "bar": "baz" }, "quux": null }),
+               mapping(&[("foo", mapping(&[("bar", to_value("baz"))])),
+                         ("quux", Value::Null)]));
+}
+
+#[test]
+fn test_keys() {
+    let id = 42;
+    assert_eq!(yaml!({ (id): true, "name": "x", type: null }),
+               mapping(&[("42", Value::Bool(true)),
+                         ("name", to_value("x")),
+                         ("type", Value::Null)]));
+}
+
+#[test]
+fn test_expr_insertion() {
+    struct User { id: i32, name: &'static str }
+    let user = User { id: 7, name: "ferris" };
+    let yaml = yaml!({ "id": user.id, "name": user.name.to_uppercase(), "paren": (user.id) });
+    assert_eq!(yaml.get("id").and_then(|y| y.as_i64()), Some(7));
+    assert_eq!(yaml.get("name").and_then(|y| y.as_str()), Some("FERRIS"));
+    assert_eq!(yaml.get("paren").and_then(|y| y.as_i64()), Some(7));
+    assert_eq!(yaml!([user.id, -user.id]), yaml!([7, -7]));
+}
+
+#[test]
+fn test_spreads() {
+    let base = yaml!({ "a": 1, "b": 2 });
+    assert_eq!(yaml!({ ..base.clone(), "b": 3, "c": 4 }), yaml!({ "a": 1, "b": 3, "c": 4 }));
+    assert_eq!(yaml!({ "b": 3, ..base.clone() }), base);
+    let rest = [3, 4];
+    assert_eq!(yaml!([1, 2, ..rest.iter(), 5]), yaml!([1, 2, 3, 4, 5]));
+}
+
+#[test]
+#[should_panic(expected = "yaml!: `..` expects an object value")]
+fn test_object_spread_non_object() {
+    let base = yaml!([1, 2]);
+    yaml!({ ..base });
+}
+
+#[test]
+fn test_optional_entries_and_guards() {
+    let some = Some("ferris");
+    let none: Option<&str> = None;
+    assert_eq!(yaml!({ "nickname"?: some, "alias"?: none, name: "x" }),
+               yaml!({ "nickname": "ferris", "name": "x" }));
+    for &dev in &[true, false] {
+        let url = if dev { "localhost" } else { "example.com" };
+        assert_eq!(yaml!({ "url": "example.com", "url": "localhost" if dev, "debug": dev if dev }),
+                   yaml!({ "url": url, "debug": true if dev }));
+    }
+}
+
+#[test]
+fn test_comprehensions() {
+    let mut map = BTreeMap::new();
+    map.insert("a", 1);
+    map.insert("b", 2);
+    assert_eq!(yaml!({ for (k, v) in &map => (k): [for i in 0..*v => i] }),
+               yaml!({ "a": [0], "b": [0, 1] }));
+    assert_eq!(yaml!([for (k, v) in &map => { "key": k, "value": v } if *v > 1]),
+               yaml!([{ "key": "b", "value": 2 }]));
+}
+
+#[test]
+fn test_document_order() {
+    let ports = [80, 443];
+    let doc = yaml!({ "name": "web", "ports": [..ports.iter()], "env": { "b": 1, "a": null } });
+    assert_eq!(serde_yaml::to_string(&doc).unwrap(),
+               "name: web\nports:\n- 80\n- 443\nenv:\n  b: 1\n  a: null\n");
+}
+
+#[test]
+fn test_hoisted_constants() {
+    let mut seen = vec![];
+    for i in 0..2 {
+        let mut value = yaml!({ "i": i, "config": { "retries": [1, 2.5] } });
+        value["config"].as_mapping_mut().unwrap().insert(to_value("changed"), Value::Bool(true));
+        seen.push(value);
+    }
+    assert_eq!(seen[1], yaml!({ "i": 1, "config": { "retries": [1, 2.5], "changed": true } }));
+}
+
+struct Unrepresentable;
+
+impl Serialize for Unrepresentable {
+    fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("unrepresentable"))
+    }
+}
+
+#[test]
+#[should_panic(expected = "yaml!: cannot convert the value at $.items[1]: unrepresentable")]
+fn test_conversion_failure_path() {
+    yaml!({ "items": [0, Unrepresentable] });
+}